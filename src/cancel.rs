@@ -0,0 +1,27 @@
+// analyzer/src/cancel.rs
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 取消令牌：编辑器在新的按键到来时调用 `cancel`，
+/// 正在进行的分析会在下一个检查点提前返回。
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 分析被取消时返回的结果。被取消的分析不产生任何可用的产物。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
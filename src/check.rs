@@ -0,0 +1,246 @@
+// analyzer/src/check.rs
+
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::db::AnalyzerDb;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::index::{crate_module_path, crate_relative_file_name};
+use crate::lint::NamingConfig;
+use crate::pass::PassRegistry;
+use crate::suggest::closest_match;
+use crate::resolver::{DefMap, NamespaceMode, Resolver};
+use nyanc_core::{FileId, Span, Symbol};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// 一次完整分析的结果：收集到的定义，以及过程中产生的所有诊断。
+#[derive(Debug, Default)]
+pub struct CheckResult {
+    pub def_map: DefMap,
+    pub diagnostics: Vec<Diagnostic>,
+    /// 只有在 `CheckOptions::profile` 打开时才会有值
+    pub timings: Option<PhaseTimings>,
+    /// 所有解析失败的名字。目前只有 `use` 路径会被解析，所以只包含导入
+    pub unresolved: Vec<(FileId, Span, Symbol)>,
+}
+
+/// 各个分析阶段的耗时，用于定位大项目中分析慢在哪里。
+/// 目前只有定义收集一个阶段；降级和类型推断落地后在这里加上对应的字段。
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    /// 整个定义收集阶段的耗时
+    pub collection: Duration,
+    /// 每个文件的收集耗时
+    pub per_file: HashMap<FileId, Duration>,
+}
+
+impl CheckResult {
+    /// 所有诊断，包括警告和提示（适合编辑器）
+    pub fn all(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    /// 只有错误（适合 CI 构建）
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diag| diag.is_error())
+    }
+
+    /// 严重程度不低于 `min` 的诊断
+    pub fn at_least(&self, min: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(move |diag| diag.severity.at_least(min))
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// 按文件分组的诊断，供编辑器的问题面板逐文件更新标记。
+    /// 不属于任何文件的 crate 级诊断不在其中，见 `crate_diagnostics`。
+    pub fn diagnostics_by_file(&self) -> HashMap<FileId, Vec<Diagnostic>> {
+        let mut by_file: HashMap<FileId, Vec<Diagnostic>> = HashMap::new();
+        for diag in &self.diagnostics {
+            if let Some(file_id) = diag.file_id {
+                by_file.entry(file_id).or_default().push(diag.clone());
+            }
+        }
+        by_file
+    }
+
+    /// crate 级别的诊断（例如多个 `main` 函数），它们没有所属的文件
+    pub fn crate_diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diag| diag.file_id.is_none())
+    }
+
+    /// 解析失败的名字及其位置，供 “did you mean” / “import X” 一类的快速修复使用
+    pub fn unresolved_names(&self) -> Vec<(FileId, Span, Symbol)> {
+        self.unresolved.clone()
+    }
+}
+
+/// 控制一次分析行为的选项。
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// 设置后，分析会在文件之间检查它，并在被取消时提前返回
+    pub cancel: Option<CancellationToken>,
+    /// 记录各阶段耗时，结果放在 `CheckResult::timings`
+    pub profile: bool,
+    /// 命名规范 lint，默认关闭
+    pub naming: Option<NamingConfig>,
+    /// 判断导入和本地定义是否冲突时使用的命名空间模型
+    pub namespaces: NamespaceMode,
+    /// 把所有警告提升为错误（严格构建），在所有 pass 运行完之后统一处理
+    pub deny_warnings: bool,
+}
+
+/// 分析整个 crate 的入口点：收集定义并汇总诊断。
+pub fn check_crate<DB: ?Sized + AnalyzerDb>(db: &DB, entry_file: FileId) -> CheckResult {
+    check_crate_with(db, entry_file, &CheckOptions::default())
+        .expect("analysis without a cancellation token cannot be cancelled")
+}
+
+/// 带选项的 `check_crate`，运行 `PassRegistry::from_options` 给出的内建 pass。
+pub fn check_crate_with<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    entry_file: FileId,
+    options: &CheckOptions,
+) -> Result<CheckResult, Cancelled> {
+    check_crate_with_passes(db, entry_file, options, &PassRegistry::from_options(options))
+}
+
+/// 只关心 crate 中的一部分文件时使用：仍然收集完整的 DefMap（解析需要它），
+/// 但只保留属于 `allow` 中文件的诊断，以及 crate 级别的诊断。
+/// 目前没有函数体的降级和推断，所以省下的只是报告；它们落地后应当只对 `allow` 中的文件运行。
+pub fn check_crate_subset<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    entry_file: FileId,
+    allow: &HashSet<FileId>,
+) -> CheckResult {
+    let mut result = check_crate(db, entry_file);
+    result.diagnostics.retain(|diag| diag.file_id.is_none_or(|file_id| allow.contains(&file_id)));
+    result.unresolved.retain(|(file_id, _, _)| allow.contains(file_id));
+    result
+}
+
+/// 收集定义之后运行 `passes` 中的 pass，而不是内建的那一组。
+pub fn check_crate_with_passes<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    entry_file: FileId,
+    options: &CheckOptions,
+    passes: &PassRegistry<DB>,
+) -> Result<CheckResult, Cancelled> {
+    let mut resolver = Resolver::new(db);
+    if let Some(token) = &options.cancel {
+        resolver = resolver.with_cancellation(token);
+    }
+    if options.profile {
+        resolver = resolver.with_profiling();
+    }
+
+    let collection_started = Instant::now();
+    resolver.collect_crate(entry_file)?;
+    let timings = options.profile.then(|| PhaseTimings {
+        collection: collection_started.elapsed(),
+        per_file: resolver.take_file_timings(),
+    });
+    let unresolved = resolver.take_unresolved_names();
+    let (def_map, mut diagnostics) = resolver.finish();
+    suggest_unresolved_names(db, &def_map, &unresolved, &mut diagnostics);
+    diagnostics.extend(passes.run(db, &def_map));
+    if options.deny_warnings {
+        for diag in &mut diagnostics {
+            if diag.severity == Severity::Warning {
+                diag.severity = Severity::Error;
+            }
+        }
+    }
+
+    Ok(CheckResult { def_map, diagnostics, timings, unresolved })
+}
+
+/// 为每个解析失败的 `use` 路径段，在能从那个位置解析到的模块名里寻找近似的拼写，
+/// 找到时追加一条 “did you mean” 提示。候选只有出错那一段的父模块下已经收集到的模块：
+/// 普通路径相对于所在文件的目录，`crate::` 路径相对于 crate 根。函数和结构体不是候选，
+/// 因为 `use` 只能引入模块。
+fn suggest_unresolved_names<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    def_map: &DefMap,
+    unresolved: &[(FileId, Span, Symbol)],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if unresolved.is_empty() {
+        return;
+    }
+
+    let modules: Vec<(FileId, String)> = def_map.files.keys()
+        .map(|file_id| (*file_id, crate_module_path(db, def_map, *file_id)))
+        .collect();
+
+    for (file_id, span, name) in unresolved {
+        let Some(parent) = parent_module_of_segment(db, def_map, *file_id, *span) else {
+            continue;
+        };
+        let candidates: Vec<&str> = modules.iter()
+            .filter(|(module_file, _)| module_file != file_id)
+            .filter_map(|(_, module)| child_of(module, &parent))
+            .collect();
+
+        let name = db.lookup_symbol(*name);
+        if let Some(candidate) = closest_match(&name, candidates) {
+            diagnostics.push(
+                Diagnostic::hint(format!("did you mean `{}`?", candidate)).at(*file_id, *span),
+            );
+        }
+    }
+}
+
+/// `module` 是 `parent` 的直接子模块时返回子模块的名字
+fn child_of<'a>(module: &'a str, parent: &str) -> Option<&'a str> {
+    let rest = if parent.is_empty() {
+        module
+    } else {
+        module.strip_prefix(parent)?.strip_prefix("::")?
+    };
+    (!rest.is_empty() && !rest.contains("::")).then_some(rest)
+}
+
+/// 找到 `file_id` 中起始于 `span` 的那个 `use` 路径段，返回它的父模块相对于 crate 根的路径
+fn parent_module_of_segment<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    def_map: &DefMap,
+    file_id: FileId,
+    span: Span,
+) -> Option<String> {
+    let ast = db.ast(file_id)?;
+    let (path, index) = ast.items.iter()
+        .filter_map(|item| match item {
+            ast::Item::Use(use_stmt) => find_segment(&use_stmt.tree, span),
+            _ => None,
+        })
+        .next()?;
+
+    let prefix = &path.segments[..index];
+    let (mut parent, prefix) = match prefix.first() {
+        Some(first) if first.lexeme == "crate" => (Vec::new(), &prefix[1..]),
+        _ => (anchor_dir_module(db, def_map, file_id), prefix),
+    };
+    parent.extend(prefix.iter().map(|segment| segment.lexeme.clone()));
+    Some(parent.join("::"))
+}
+
+fn find_segment(tree: &ast::UseTree, span: Span) -> Option<(&ast::Path, usize)> {
+    match tree {
+        ast::UseTree::Simple { path, .. } => path.segments.iter()
+            .position(|segment| segment.span.start == span.start)
+            .map(|index| (path, index)),
+        ast::UseTree::Group { items } => items.iter().find_map(|item| find_segment(item, span)),
+        ast::UseTree::Wildcard { .. } => None,
+    }
+}
+
+/// 文件所在目录对应的模块路径（相对于 crate 根所在的目录），普通 `use` 路径以它为起点
+fn anchor_dir_module<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, file_id: FileId) -> Vec<String> {
+    let relative = crate_relative_file_name(db, def_map, file_id);
+    match relative.rsplit_once('/') {
+        Some((dir, _)) => dir.split('/').map(str::to_string).collect(),
+        None => Vec::new(),
+    }
+}
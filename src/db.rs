@@ -1,13 +1,277 @@
+use crate::line_index::LineIndex;
 use ast::Path; 
 use nyanc_core::{FileId, Symbol};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 /// 这个 Trait 定义了所有分析（解析、类型检查等）过程
 /// 所需要向“数据库”（即 CompilationContext）查询的所有能力。
+///
+/// analyzer 只通过 FileId 和这些查询访问文件，从不直接接触文件系统。
+/// 因此一个 FileId 完全可以指向编辑器中未保存的缓冲区或者代码生成器产出的内存源码，
+/// 只要 `resolve_module` 能解析到它、`ast` / `source_text` 能给出它的内容即可（见 `Overlay`）。
 pub trait AnalyzerDb {
-    fn ast(&self, file_id: FileId) -> Arc<ast::Module>;
+    /// 文件的 AST。文件无法读取或者解析失败时返回 `None`，
+    /// analyzer 会把它记为一条诊断，并继续分析其余的文件。
+    fn ast(&self, file_id: FileId) -> Option<Arc<ast::Module>>;
+    /// 文件的源码文本，用于把 Span 换算成行号等
+    fn source_text(&self, file_id: FileId) -> Arc<String>;
+    /// 文件的行首偏移表。默认每次都从 `source_text` 重新构建，
+    /// 需要频繁换算位置的 db（例如语言服务器）应当缓存它
+    fn line_index(&self, file_id: FileId) -> Arc<LineIndex> {
+        Arc::new(LineIndex::new(&self.source_text(file_id)))
+    }
+    /// 把 `use` 路径解析为模块文件。
+    /// 对同一个 `(anchor_file, path)` 的结果允许被缓存（见 `ResolveCache`），
+    /// 只要在文件布局变化时调用 `clear_resolve_cache` 即可。
     fn resolve_module(&self, anchor_file: FileId, path: &Path) -> Option<FileId>;
+    /// 所有可能对应模块 `name` 的文件（例如 `foo.ny` 和 `foo/mod.ny`）。
+    /// `name` 是相对于 `anchor_file` 所在目录、用 `/` 连接的模块路径，例如 `geo/shapes`。
+    /// 默认返回空列表，表示 db 不提供文件列表，解析完全交给 `resolve_module`。
+    fn module_candidates(&self, _anchor_file: FileId, _name: &str) -> Vec<FileId> {
+        Vec::new()
+    }
+    /// 和 `resolve_module` 一样，但失败时说明原因，供 analyzer 给出更准确的诊断。
+    /// 默认实现先看 `module_candidates`：多个候选即为歧义；没有候选时退回到 `resolve_module`，
+    /// 这时只能区分找到和没找到。能察觉非法路径的 db 应当覆盖它。
+    ///
+    /// 默认实现不缓存，每次调用都要拼接出候选名。需要反复解析同一批路径的 db
+    /// 应当覆盖它并使用 `ResolveCache`，这样同一条路径只在第一次解析时分配内存。
+    fn resolve_module_detailed(&self, anchor_file: FileId, path: &Path) -> ModuleResolution {
+        let name = path.segments.iter()
+            .map(|s| s.lexeme.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        match self.module_candidates(anchor_file, &name).as_slice() {
+            [] => {}
+            [file_id] => return ModuleResolution::Found(*file_id),
+            candidates => return ModuleResolution::Ambiguous(candidates.to_vec()),
+        }
+        match self.resolve_module(anchor_file, path) {
+            Some(file_id) => ModuleResolution::Found(file_id),
+            // 不知道具体哪一段出错时，归咎于最后一段
+            None => ModuleResolution::NotFound {
+                segment: path.segments.len().saturating_sub(1),
+            },
+        }
+    }
+    /// 与 `resolve_module_detailed` 相同，但只解析 `path` 从第 `start` 段开始的部分。
+    /// analyzer 用它解析 `crate::` 路径：锚点是 crate 根，开头的 `crate` 段不参与解析。
+    /// 返回的 `NotFound::segment` 是完整路径中的下标。
+    ///
+    /// 默认实现复制出剩下的段再调用 `resolve_module_detailed`；覆盖了后者的 db
+    /// 通常也应当覆盖它（见 `ResolveCache::get_or_resolve_from`），以免每次都复制。
+    fn resolve_module_from(&self, anchor_file: FileId, path: &Path, start: usize) -> ModuleResolution {
+        let rest = Path { segments: path.segments[start..].to_vec() };
+        match self.resolve_module_detailed(anchor_file, &rest) {
+            ModuleResolution::NotFound { segment } => ModuleResolution::NotFound { segment: segment + start },
+            resolution => resolution,
+        }
+    }
+    /// 文件被添加或删除后调用，丢弃所有缓存的 `resolve_module` 结果。
+    /// 不缓存的实现无需覆盖它。
+    fn clear_resolve_cache(&self) {}
     /// 将一个字符串切片转换为一个唯一的 Symbol
     fn intern_string(&self, s: &str) -> Symbol;
+    /// `intern_string` 的逆操作：根据 Symbol 取回原始字符串
+    fn lookup_symbol(&self, symbol: Symbol) -> String;
+    /// 返回文件的显示名（例如 `utils.ny`），用于快照和诊断输出
+    fn file_name(&self, file_id: FileId) -> String;
     // fn def_map(&self) -> Arc<DefMap>;
-}
\ No newline at end of file
+}
+
+/// 一次模块解析的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleResolution {
+    Found(FileId),
+    /// 没有任何文件对应这个路径。`segment` 是第一个无法解析的段的下标，
+    /// 例如 `a::b::c` 中 `a` 下面没有 `b` 时为 1
+    NotFound { segment: usize },
+    /// 路径同时对应多个文件（例如 `x.ny` 和 `x/mod.ny`）
+    Ambiguous(Vec<FileId>),
+    /// 路径本身不合法（例如空路径）
+    Malformed,
+}
+
+impl ModuleResolution {
+    /// 解析到的文件；除 `Found` 以外都是 `None`
+    pub fn file(&self) -> Option<FileId> {
+        match self {
+            ModuleResolution::Found(file_id) => Some(*file_id),
+            _ => None,
+        }
+    }
+}
+
+/// 驻留后的 `use` 路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathId(u32);
+
+/// 把 `use` 路径驻留成 `PathId`。
+/// 每条不同的路径只在第一次出现时复制一份段列表，之后查询同一条路径不会分配内存。
+#[derive(Debug, Default)]
+pub struct PathInterner {
+    /// 段列表的哈希 -> 具有这个哈希的路径
+    buckets: RefCell<HashMap<u64, Vec<PathId>>>,
+    paths: RefCell<Vec<Vec<String>>>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&self, path: &Path) -> PathId {
+        self.intern_from(path, 0)
+    }
+
+    /// 只驻留 `path` 从第 `start` 段开始的部分
+    pub fn intern_from(&self, path: &Path, start: usize) -> PathId {
+        let segments = &path.segments[start..];
+        let mut hasher = DefaultHasher::new();
+        for segment in segments {
+            segment.lexeme.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        let same_segments = |stored: &Vec<String>| {
+            stored.len() == segments.len()
+                && stored.iter().zip(segments).all(|(a, b)| *a == b.lexeme)
+        };
+        if let Some(bucket) = self.buckets.borrow().get(&hash) {
+            let paths = self.paths.borrow();
+            if let Some(id) = bucket.iter().find(|id| same_segments(&paths[id.0 as usize])) {
+                return *id;
+            }
+        }
+
+        let mut paths = self.paths.borrow_mut();
+        let id = PathId(paths.len() as u32);
+        paths.push(segments.iter().map(|s| s.lexeme.clone()).collect());
+        self.buckets.borrow_mut().entry(hash).or_default().push(id);
+        id
+    }
+
+    /// 驻留时记录的段
+    pub fn segments(&self, id: PathId) -> Vec<String> {
+        self.paths.borrow()[id.0 as usize].clone()
+    }
+
+    /// 已驻留的不同路径的数量
+    pub fn len(&self) -> usize {
+        self.paths.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.borrow().is_empty()
+    }
+}
+
+/// `resolve_module_detailed` 的结果缓存，db 实现可以直接内嵌使用。
+/// 失败的解析同样会被缓存，所以文件布局变化后必须 `clear`。
+#[derive(Debug, Default)]
+pub struct ResolveCache {
+    paths: PathInterner,
+    entries: RefCell<HashMap<(FileId, PathId), ModuleResolution>>,
+}
+
+impl ResolveCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 命中缓存时直接返回，否则调用 `resolve` 并记录结果
+    pub fn get_or_resolve(
+        &self,
+        anchor_file: FileId,
+        path: &Path,
+        resolve: impl FnOnce() -> ModuleResolution,
+    ) -> ModuleResolution {
+        self.get_or_resolve_from(anchor_file, path, 0, resolve)
+    }
+
+    /// 与 `get_or_resolve` 相同，但以 `path` 从第 `start` 段开始的部分为键（见 `resolve_module_from`）。
+    /// `resolve` 给出的和缓存的 `NotFound::segment` 都相对于这一部分，返回时换算成完整路径中的下标
+    pub fn get_or_resolve_from(
+        &self,
+        anchor_file: FileId,
+        path: &Path,
+        start: usize,
+        resolve: impl FnOnce() -> ModuleResolution,
+    ) -> ModuleResolution {
+        let key = (anchor_file, self.paths.intern_from(path, start));
+        let cached = self.entries.borrow().get(&key).cloned();
+        let resolved = cached.unwrap_or_else(|| {
+            let resolved = resolve();
+            self.entries.borrow_mut().insert(key, resolved.clone());
+            resolved
+        });
+        match resolved {
+            ModuleResolution::NotFound { segment } => ModuleResolution::NotFound { segment: segment + start },
+            resolution => resolution,
+        }
+    }
+
+    /// 丢弃缓存的结果。驻留的路径保留，它们与文件布局无关
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    /// 缓存见过的不同路径的数量（每条路径只分配一次）
+    pub fn interned_paths(&self) -> usize {
+        self.paths.len()
+    }
+}
+
+
+/// 不由磁盘支撑的“覆盖层”文件：编辑器未保存的缓冲区、生成的代码等。
+/// db 实现可以内嵌它，在查询文件系统之前先查询覆盖层。
+#[derive(Debug, Default)]
+pub struct Overlay {
+    paths: HashMap<String, FileId>,
+    sources: HashMap<FileId, Arc<String>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以 `path` 为名放入（或替换）一个内存文件
+    pub fn insert(&mut self, path: &str, file_id: FileId, source: &str) {
+        self.paths.insert(path.to_string(), file_id);
+        self.sources.insert(file_id, Arc::new(source.to_string()));
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<FileId> {
+        let file_id = self.paths.remove(path)?;
+        self.sources.remove(&file_id);
+        Some(file_id)
+    }
+
+    pub fn file_id(&self, path: &str) -> Option<FileId> {
+        self.paths.get(path).copied()
+    }
+
+    pub fn source(&self, file_id: FileId) -> Option<Arc<String>> {
+        self.sources.get(&file_id).cloned()
+    }
+
+    pub fn path(&self, file_id: FileId) -> Option<&str> {
+        self.paths
+            .iter()
+            .find(|(_, id)| **id == file_id)
+            .map(|(path, _)| path.as_str())
+    }
+}
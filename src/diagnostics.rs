@@ -0,0 +1,70 @@
+// analyzer/src/diagnostics.rs
+
+use nyanc_core::{FileId, Span};
+
+/// 诊断的严重程度。顺序从高到低：`Error` 最严重。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Hint,
+}
+
+impl Severity {
+    /// 是否至少和 `min` 一样严重，例如 `Warning.at_least(Hint)` 成立，`Warning.at_least(Error)` 不成立
+    pub fn at_least(self, min: Severity) -> bool {
+        self <= min
+    }
+}
+
+/// analyzer 产生的一条诊断信息。
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 诊断所属的文件；crate 级别的诊断没有文件
+    pub file_id: Option<FileId>,
+    pub span: Option<Span>,
+    /// 稳定的诊断代码（例如 `unresolved-import`），供编辑器和文档引用
+    pub code: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            file_id: None,
+            span: None,
+            code: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn hint(message: impl Into<String>) -> Self {
+        Self::new(Severity::Hint, message)
+    }
+
+    /// 把诊断定位到某个文件中的某个位置
+    pub fn at(mut self, file_id: FileId, span: Span) -> Self {
+        self.file_id = Some(file_id);
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
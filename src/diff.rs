@@ -0,0 +1,40 @@
+// analyzer/src/diff.rs
+
+use crate::db::AnalyzerDb;
+use crate::index::qualified_name;
+use crate::resolver::{DefMap, Namespace};
+use std::collections::BTreeSet;
+
+/// 两个 DefMap 之间的差异，定义按带模块前缀的完整名字和命名空间匹配，
+/// 这样重新收集导致的 DefId 变化不会被当成改动，同名的结构体和函数也不会互相覆盖。
+/// 每个列表都按名字排序。
+///
+/// 签名和字段还没有降级，所以还无法判断同名的定义是否发生了变化，这里只报告增加和删除。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefMapDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl DefMapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// 计算从 `old` 到 `new` 的差异
+pub fn diff<DB: ?Sized + AnalyzerDb>(old: &DefMap, new: &DefMap, db: &DB) -> DefMapDiff {
+    let old_defs = by_qualified_name(old, db);
+    let new_defs = by_qualified_name(new, db);
+
+    DefMapDiff {
+        added: new_defs.difference(&old_defs).map(|(name, _)| name.clone()).collect(),
+        removed: old_defs.difference(&new_defs).map(|(name, _)| name.clone()).collect(),
+    }
+}
+
+fn by_qualified_name<DB: ?Sized + AnalyzerDb>(def_map: &DefMap, db: &DB) -> BTreeSet<(String, Namespace)> {
+    def_map.iter()
+        .map(|(_, item_def)| (qualified_name(db, def_map, item_def), item_def.kind.namespace()))
+        .collect()
+}
@@ -0,0 +1,140 @@
+// analyzer/src/index.rs
+
+use crate::db::AnalyzerDb;
+use crate::resolver::{DefMap, ItemDef, ItemKind};
+use hir::DefId;
+use nyanc_core::{FileId, Span};
+use serde::Serialize;
+
+/// 符号索引中的一项，供 ctags 一类的外部工具使用。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SymbolEntry {
+    /// 带模块前缀的完整名字，例如 `utils::Point`
+    pub name: String,
+    pub kind: &'static str,
+    pub file: String,
+    /// 从 1 开始的行号
+    pub line: u32,
+}
+
+/// 导出整个 crate 的符号索引，按 DefId 升序排列。
+pub fn export_symbol_index<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap) -> Vec<SymbolEntry> {
+    def_map.iter()
+        .map(|(_, item_def)| {
+            let file = db.file_name(item_def.file_id);
+            let (line, _) = db.line_index(item_def.file_id).line_col(item_def.span.start);
+            SymbolEntry {
+                name: qualified_name(db, def_map, item_def),
+                kind: item_def.kind.keyword(),
+                line: line + 1,
+                file,
+            }
+        })
+        .collect()
+}
+
+/// 带模块前缀的完整名字，例如 `utils::Point`。模块路径相对于 crate 根（见 `crate_module_path`），
+/// 所以根文件中的定义没有前缀，并且结果总能被 `resolve_def_path` 解析回来。
+pub fn qualified_name<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, item_def: &ItemDef) -> String {
+    let module = crate_module_path(db, def_map, item_def.file_id);
+    let name = db.lookup_symbol(item_def.name);
+    if module.is_empty() {
+        name
+    } else {
+        format!("{}::{}", module, name)
+    }
+}
+
+/// 由文件名推出模块路径：`geo/shapes.ny` -> `geo::shapes`。
+/// 目录模块 `geo/mod.ny` 就是 `geo`
+pub fn module_path(file_name: &str) -> String {
+    let path = file_name.trim_end_matches(".ny");
+    let path = match path.strip_suffix("mod") {
+        Some("") => "",
+        Some(dir) if dir.ends_with('/') => dir.trim_end_matches('/'),
+        _ => path,
+    };
+    path.replace('/', "::")
+}
+
+/// 文件相对于 crate 根所在目录的模块路径。根文件本身的模块路径为空。
+/// 例如根为 `src/main.ny` 时，`src/geo/shapes.ny` 的模块路径是 `geo::shapes`。
+pub fn crate_module_path<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, file_id: FileId) -> String {
+    if def_map.root == Some(file_id) {
+        return String::new();
+    }
+    module_path(&crate_relative_file_name(db, def_map, file_id))
+}
+
+/// 文件相对于 crate 根所在目录的文件名，例如根为 `src/main.ny` 时 `src/geo/shapes.ny` 得到 `geo/shapes.ny`。
+/// 不在根目录之下的文件原样返回。模块路径和 `use` 路径都以这个目录为 crate 的起点
+pub fn crate_relative_file_name<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, file_id: FileId) -> String {
+    let file_name = db.file_name(file_id);
+    let root_dir = def_map.root
+        .map(|root| db.file_name(root))
+        .and_then(|root_name| root_name.rsplit_once('/').map(|(dir, _)| format!("{}/", dir)))
+        .unwrap_or_default();
+    match file_name.strip_prefix(&root_dir) {
+        Some(relative) => relative.to_string(),
+        None => file_name,
+    }
+}
+
+/// 把 `"utils::Point"` 这样的文本路径解析为 DefId，方便脚本和命令行工具使用。
+/// 最后一段是定义的名字，前面的部分是相对于 crate 根的模块路径；可以带前导的 `crate::`。
+pub fn resolve_def_path<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, path: &str) -> Option<DefId> {
+    let path = path.strip_prefix("crate::").unwrap_or(path);
+    let (module, name) = match path.rsplit_once("::") {
+        Some((module, name)) => (module, name),
+        None => ("", path),
+    };
+    let name = db.intern_string(name);
+
+    let mut files: Vec<FileId> = def_map.files.keys().copied().collect();
+    files.sort();
+    let file_id = files.into_iter()
+        .find(|file_id| crate_module_path(db, def_map, *file_id) == module)?;
+
+    def_map.files[&file_id].items.iter()
+        .copied()
+        .find(|def_id| def_map.items.get(def_id).is_some_and(|item_def| item_def.name == name))
+}
+
+/// 标识符的语义类别，供编辑器的语义高亮使用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticKind {
+    Item(ItemKind),
+    /// `use` 路径中的模块名
+    Module,
+}
+
+/// 位于 `offset` 处的标识符的语义类别。
+/// 目前能识别的是定义的名字和 `use` 路径中的各段；函数体没有被降级，所以局部变量还无法识别。
+pub fn kind_at<DB: ?Sized + AnalyzerDb>(db: &DB, def_map: &DefMap, file_id: FileId, offset: usize) -> Option<SemanticKind> {
+    let contains = |span: Span| span.start <= offset && offset < span.end;
+
+    let file_defs = def_map.files.get(&file_id)?;
+    let item = file_defs.items.iter()
+        .filter_map(|def_id| def_map.items.get(def_id))
+        .find(|item_def| contains(item_def.span));
+    if let Some(item_def) = item {
+        return Some(SemanticKind::Item(item_def.kind));
+    }
+
+    let ast = db.ast(file_id)?;
+    ast.items.iter()
+        .filter_map(|item| match item {
+            ast::Item::Use(use_stmt) => Some(&use_stmt.tree),
+            _ => None,
+        })
+        .any(|tree| use_tree_contains(tree, &contains))
+        .then_some(SemanticKind::Module)
+}
+
+fn use_tree_contains(tree: &ast::UseTree, contains: &impl Fn(Span) -> bool) -> bool {
+    match tree {
+        ast::UseTree::Simple { path, .. } => path.segments.iter().any(|segment| contains(segment.span)),
+        ast::UseTree::Group { items } => items.iter().any(|item| use_tree_contains(item, contains)),
+        ast::UseTree::Wildcard { .. } => false,
+    }
+}
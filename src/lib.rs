@@ -1,8 +1,26 @@
+pub mod cancel;
+pub mod check;
 pub mod db;
+pub mod diagnostics;
+pub mod diff;
+pub mod index;
+pub mod line_index;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod pass;
 pub mod resolver;
+pub mod suggest;
 pub mod ty;
+pub mod visit;
 #[cfg(test)]
 mod tests;
 
-pub use db::AnalyzerDb;
-pub use resolver::{DefMap, Resolver};
\ No newline at end of file
+pub use cancel::{CancellationToken, Cancelled};
+pub use check::{check_crate, check_crate_subset, check_crate_with, check_crate_with_passes, CheckOptions, CheckResult, PhaseTimings};
+pub use db::{AnalyzerDb, ModuleResolution, Overlay, PathId, PathInterner, ResolveCache};
+pub use diagnostics::{Diagnostic, Severity};
+pub use line_index::LineIndex;
+pub use pass::{AnalysisPass, PassRegistry};
+pub use resolver::{import_closure, reachable_files, Binding, CrateId, DefMap, DefMapBuilder, GlobalDefId, ImportClosure, Namespace, NamespaceMode, QualifiedCache, Resolver, ResolverSnapshot};
+pub use visit::DefVisitor;
\ No newline at end of file
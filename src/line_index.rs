@@ -0,0 +1,66 @@
+// analyzer/src/line_index.rs
+
+/// 一个文件的行首偏移表，用于把字节偏移换算成 (行, 列)。
+/// 构造一次之后每次查询都是二分查找，适合对同一个文件做大量换算（例如给编辑器发送诊断）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// 每一行第一个字节的偏移，第一项总是 0
+    line_starts: Vec<usize>,
+    /// 所有多字节字符，按偏移升序。换算 UTF-16 列时只需要扣掉它们多出来的长度
+    wide_chars: Vec<WideChar>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    offset: usize,
+    len_utf8: usize,
+    /// UTF-8 长度比 UTF-16 长度多出的部分
+    excess: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        let wide_chars = text.char_indices()
+            .filter(|(_, ch)| !ch.is_ascii())
+            .map(|(offset, ch)| WideChar {
+                offset,
+                len_utf8: ch.len_utf8(),
+                excess: ch.len_utf8() - ch.len_utf16(),
+            })
+            .collect();
+        Self { line_starts, wide_chars, len: text.len() }
+    }
+
+    /// 把字节偏移换算成从 0 开始的 (行, 列)。列按字节计算；超出文件末尾的偏移按末尾处理
+    pub fn line_col(&self, offset: usize) -> (u32, u32) {
+        let offset = offset.min(self.len);
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        (line as u32, (offset - self.line_starts[line]) as u32)
+    }
+
+    /// 与 `line_col` 相同，但列按 UTF-16 code unit 计算，这是 LSP 默认的位置编码。
+    /// 落在多字节字符中间的偏移（例如源码改动后过期的 span）按那个字符的开头处理
+    pub fn line_col_utf16(&self, offset: usize) -> (u32, u32) {
+        let offset = offset.min(self.len);
+        let containing = self.wide_chars.partition_point(|wide| wide.offset < offset)
+            .checked_sub(1)
+            .map(|index| self.wide_chars[index])
+            .filter(|wide| offset < wide.offset + wide.len_utf8);
+        let offset = containing.map_or(offset, |wide| wide.offset);
+
+        let (line, col) = self.line_col(offset);
+        let line_start = self.line_starts[line as usize];
+        let first = self.wide_chars.partition_point(|wide| wide.offset < line_start);
+        let last = self.wide_chars.partition_point(|wide| wide.offset < offset);
+        let excess: usize = self.wide_chars[first..last].iter().map(|wide| wide.excess).sum();
+        (line, col - excess as u32)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
@@ -0,0 +1,92 @@
+// analyzer/src/lint.rs
+
+use crate::db::AnalyzerDb;
+use crate::diagnostics::Diagnostic;
+use crate::resolver::{DefMap, ItemKind};
+
+/// 命名风格
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseStyle {
+    /// `snake_case`
+    SnakeCase,
+    /// `PascalCase`
+    PascalCase,
+}
+
+impl CaseStyle {
+    /// 名字是否符合这种风格。前导下划线会被忽略（例如 `_unused`）。
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim_start_matches('_');
+        if name.is_empty() {
+            return true;
+        }
+        match self {
+            CaseStyle::SnakeCase => name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+            CaseStyle::PascalCase => {
+                name.starts_with(|c: char| c.is_ascii_uppercase())
+                    && name.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaseStyle::SnakeCase => "snake_case",
+            CaseStyle::PascalCase => "PascalCase",
+        }
+    }
+}
+
+/// 命名规范 lint 的配置。默认：函数用 `snake_case`，类型用 `PascalCase`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamingConfig {
+    pub functions: CaseStyle,
+    pub types: CaseStyle,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        Self {
+            functions: CaseStyle::SnakeCase,
+            types: CaseStyle::PascalCase,
+        }
+    }
+}
+
+impl NamingConfig {
+    pub fn style_for(&self, kind: ItemKind) -> CaseStyle {
+        match kind {
+            ItemKind::Function => self.functions,
+            ItemKind::Struct => self.types,
+        }
+    }
+}
+
+/// 检查每个定义的名字是否符合 `config` 中它的种类所要求的风格，对不符合的给出警告。
+pub fn check_naming<DB: ?Sized + AnalyzerDb>(
+    db: &DB,
+    def_map: &DefMap,
+    config: &NamingConfig,
+) -> Vec<Diagnostic> {
+    def_map.iter()
+        .filter_map(|(_, item_def)| {
+            let style = config.style_for(item_def.kind);
+            let name = db.lookup_symbol(item_def.name);
+            (!style.matches(&name)).then(|| {
+                let what = match item_def.kind {
+                    ItemKind::Function => "function",
+                    ItemKind::Struct => "struct",
+                };
+                Diagnostic::warning(format!(
+                    "{} `{}` should have a {} name",
+                    what,
+                    name,
+                    style.name()
+                ))
+                .at(item_def.file_id, item_def.span)
+            })
+        })
+        .collect()
+}
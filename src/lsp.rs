@@ -0,0 +1,83 @@
+// analyzer/src/lsp.rs
+
+//! 把诊断转换成语言服务器协议（LSP）使用的形状。
+//! 这里只定义 `textDocument/publishDiagnostics` 需要的那部分结构，序列化后的 JSON 与协议一致，
+//! 语言服务器可以直接发送，或者转换成自己使用的 LSP 类型库中的对应结构。
+
+use crate::check::CheckResult;
+use crate::db::AnalyzerDb;
+use crate::diagnostics::{Diagnostic, Severity};
+use nyanc_core::FileId;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 文件中的一个位置，行和列都从 0 开始。
+/// `character` 按 UTF-16 code unit 计算，即协议默认的 `positionEncoding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    /// LSP 的 `DiagnosticSeverity`：1 = Error，2 = Warning，4 = Hint
+    pub severity: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    pub source: &'static str,
+    pub message: String,
+}
+
+fn lsp_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Hint => 4,
+    }
+}
+
+impl CheckResult {
+    /// 把所有属于文件的诊断按文件转换成 LSP 诊断。
+    /// crate 级别的诊断没有位置，不在结果中（见 `crate_diagnostics`）。
+    pub fn to_lsp_diagnostics<DB: ?Sized + AnalyzerDb>(&self, db: &DB) -> HashMap<FileId, Vec<LspDiagnostic>> {
+        let mut by_file: HashMap<FileId, Vec<LspDiagnostic>> = HashMap::new();
+        for diag in &self.diagnostics {
+            if let Some(file_id) = diag.file_id {
+                by_file.entry(file_id).or_default().push(to_lsp_diagnostic(db, file_id, diag));
+            }
+        }
+        by_file
+    }
+}
+
+fn to_lsp_diagnostic<DB: ?Sized + AnalyzerDb>(db: &DB, file_id: FileId, diag: &Diagnostic) -> LspDiagnostic {
+    let line_index = db.line_index(file_id);
+    let position = |offset: usize| {
+        let (line, character) = line_index.line_col_utf16(offset);
+        LspPosition { line, character }
+    };
+    // 只有文件没有 span 的诊断放在文件开头
+    let range = match diag.span {
+        Some(span) => LspRange {
+            start: position(span.start),
+            end: position(span.end),
+        },
+        None => LspRange { start: position(0), end: position(0) },
+    };
+
+    LspDiagnostic {
+        range,
+        severity: lsp_severity(diag.severity),
+        code: diag.code,
+        source: "nyanc",
+        message: diag.message.clone(),
+    }
+}
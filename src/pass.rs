@@ -0,0 +1,145 @@
+// analyzer/src/pass.rs
+
+use crate::check::CheckOptions;
+use crate::db::AnalyzerDb;
+use crate::diagnostics::Diagnostic;
+use crate::lint::{check_naming, NamingConfig};
+use crate::resolver::{DefMap, ItemKind, Namespace, NamespaceMode};
+
+/// 在定义收集完成之后运行的一个分析步骤。
+/// 每个 pass 只读取共享的 db 和 DefMap，把发现的问题作为诊断返回。
+pub trait AnalysisPass<DB: ?Sized + AnalyzerDb> {
+    /// pass 的名字，用于调试输出和按名字查找
+    fn name(&self) -> &'static str;
+    fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic>;
+}
+
+/// 一组按注册顺序运行的 pass。
+pub struct PassRegistry<DB: ?Sized + AnalyzerDb> {
+    passes: Vec<Box<dyn AnalysisPass<DB>>>,
+}
+
+impl<DB: ?Sized + AnalyzerDb> Default for PassRegistry<DB> {
+    fn default() -> Self {
+        Self { passes: Vec::new() }
+    }
+}
+
+impl<DB: ?Sized + AnalyzerDb> PassRegistry<DB> {
+    /// 一个空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `check_crate_with` 默认运行的内建 pass，按 `options` 决定启用哪些
+    pub fn from_options(options: &CheckOptions) -> Self {
+        let mut registry = Self::new();
+        registry.register(MultipleMainsPass);
+        registry.register(ImportConflictPass { mode: options.namespaces });
+        if let Some(config) = options.naming {
+            registry.register(NamingPass { config });
+        }
+        registry
+    }
+
+    /// 把 `pass` 追加到末尾
+    pub fn register(&mut self, pass: impl AnalysisPass<DB> + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// 已注册的 pass 的名字，按运行顺序
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// 依次运行所有 pass，汇总它们的诊断
+    pub fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic> {
+        self.passes
+            .iter()
+            .flat_map(|pass| pass.run(db, def_map))
+            .collect()
+    }
+}
+
+/// crate 只能有一个入口点：扫描整个 DefMap，如果有多个 `main` 函数，
+/// 报告一条列出所有位置的 crate 级错误。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MultipleMainsPass;
+
+impl<DB: ?Sized + AnalyzerDb> AnalysisPass<DB> for MultipleMainsPass {
+    fn name(&self) -> &'static str {
+        "multiple-mains"
+    }
+
+    fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic> {
+        let main = db.intern_string("main");
+        let locations: Vec<String> = def_map.iter()
+            .filter(|(_, item_def)| item_def.kind == ItemKind::Function && item_def.name == main)
+            .map(|(_, item_def)| db.file_name(item_def.file_id))
+            .collect();
+
+        if locations.len() <= 1 {
+            return Vec::new();
+        }
+        vec![Diagnostic::error(format!(
+            "multiple `main` functions found: {}",
+            locations.join(", ")
+        ))]
+    }
+}
+
+/// 检查导入的名字是否与同一文件中的本地定义冲突。
+/// 导入的模块位于类型命名空间，所以在 `Separate` 模式下只和结构体冲突。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportConflictPass {
+    pub mode: NamespaceMode,
+}
+
+impl<DB: ?Sized + AnalyzerDb> AnalysisPass<DB> for ImportConflictPass {
+    fn name(&self) -> &'static str {
+        "import-conflicts"
+    }
+
+    fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut files: Vec<_> = def_map.files.iter().collect();
+        files.sort_by_key(|(file_id, _)| **file_id);
+
+        for (file_id, file_defs) in files {
+            for def_id in &file_defs.items {
+                let item_def = &def_map.items[def_id];
+                if !file_defs.imports.contains_key(&item_def.name) {
+                    continue;
+                }
+                if self.mode.conflicts(Namespace::Type, item_def.kind.namespace()) {
+                    diagnostics.push(
+                        Diagnostic::error(format!(
+                            "`{}` is defined here but also imported in this file",
+                            db.lookup_symbol(item_def.name)
+                        ))
+                        .with_code("import-conflict")
+                        .at(*file_id, item_def.span),
+                    );
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// 命名规范 lint（见 `check_naming`）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NamingPass {
+    pub config: NamingConfig,
+}
+
+impl<DB: ?Sized + AnalyzerDb> AnalysisPass<DB> for NamingPass {
+    fn name(&self) -> &'static str {
+        "naming"
+    }
+
+    fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic> {
+        check_naming(db, def_map, &self.config)
+    }
+}
@@ -1,11 +1,17 @@
 // analyzer/src/resolver.rs
 
-use crate::db::AnalyzerDb;
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::db::{AnalyzerDb, ModuleResolution};
+use crate::diagnostics::Diagnostic;
+use crate::ty::BuiltinSignature;
 use ast::Item as AstItem; // 使用 `as` 来避免与 hir::Item 的命名冲突
-use nyanc_core::{Symbol, FileId};
+use nyanc_core::{Symbol, FileId, Span};
 use hir::DefId;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::collections::HashSet;
 use std::collections::VecDeque;
 
@@ -24,29 +30,374 @@ pub struct ItemDef {
     pub def_id: DefId,
     pub name: Symbol,
     pub kind: ItemKind, // 现在这个类型被定义了
-    pub ast_node: Arc<ast::Item>,
+    /// 定义所在的文件
+    pub file_id: FileId,
+    /// 定义名字的位置（指向名字 token，而不是整个项目）
+    pub span: Span,
+    /// 定义对应的 AST 节点。由工具合成的定义（例如 prelude）没有源码，为 `None`
+    pub ast_node: Option<Arc<ast::Item>>,
+    /// 是否是测试函数（见 `is_test_function_name`）
+    pub is_test: bool,
+}
+
+/// 通过 `Resolver::with_builtin` 注入的内建函数。
+/// 内建函数没有源码，所以不像 `ItemDef` 那样记录文件和位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinDef {
+    pub name: Symbol,
+    pub signature: BuiltinSignature,
+}
+
+/// 测试函数的命名约定：以 `test_` 开头的函数。
+/// 当前的 AST 不保留属性，所以还无法识别 `#[test]`。
+pub fn is_test_function_name(name: &str) -> bool {
+    name.starts_with("test_")
+}
+
+/// 名字所在的命名空间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Namespace {
+    /// 类型和模块
+    Type,
+    /// 函数等值
+    Value,
+}
+
+/// 语言是否区分类型命名空间和值命名空间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamespaceMode {
+    /// 类型和值各有各的命名空间，同名的结构体和函数可以共存
+    #[default]
+    Separate,
+    /// 所有名字共享一个命名空间
+    Unified,
+}
+
+impl NamespaceMode {
+    /// 两个名字相同的绑定在这种模式下是否冲突
+    pub fn conflicts(&self, a: Namespace, b: Namespace) -> bool {
+        match self {
+            NamespaceMode::Separate => a == b,
+            NamespaceMode::Unified => true,
+        }
+    }
+}
+
+impl ItemKind {
+    pub fn namespace(&self) -> Namespace {
+        match self {
+            ItemKind::Function => Namespace::Value,
+            ItemKind::Struct => Namespace::Type,
+        }
+    }
+
+    /// 在快照输出等场合使用的简短关键字
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ItemKind::Function => "fn",
+            ItemKind::Struct => "struct",
+        }
+    }
+}
+
+/// 多 crate 分析中一个 crate 的编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CrateId(pub u32);
+
+/// 带有所属 crate 的 DefId。`hir::DefId` 只在单个 crate 内唯一，
+/// 合并多个 crate 的 DefMap 时用它作为键，就不需要重新编号。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlobalDefId {
+    pub krate: CrateId,
+    pub local: DefId,
+}
+
+/// 单个文件收集到的“定义片段”。
+/// 增量更新时，一个文件的片段会被整体替换，而不会影响其他文件。
+#[derive(Debug, Clone, Default)]
+pub struct FileDefs {
+    /// 该文件中定义的所有顶层项目，按出现顺序排列
+    pub items: Vec<DefId>,
+    /// `use` 引入的名字（路径的最后一段）到其所指模块文件的映射
+    pub imports: HashMap<Symbol, FileId>,
+    /// 该文件对外可见的定义。目前还没有可见性，所以与 `items` 相同
+    pub exports: Vec<DefId>,
+    /// 收集时源码的哈希，`update_file` 用它跳过内容没有变化的文件。
+    /// 不是从源码收集的片段（例如 `DefMapBuilder` 构造的）没有哈希
+    pub source_hash: Option<u64>,
 }
 
 /// “定义地图”，整个项目中所有顶层项目（函数、结构体等）的中央登记处。
-#[derive(Debug, Default)]
+/// `items` 是由各个文件的 `FileDefs` 片段拼合而成的全局视图。
+#[derive(Debug, Clone, Default)]
 pub struct DefMap {
     pub items: HashMap<DefId, ItemDef>,
+    pub files: HashMap<FileId, FileDefs>,
+    /// crate 根文件，即收集的入口文件。`crate::` 路径以它为锚点
+    pub root: Option<FileId>,
+    /// prelude 模块：它们导出的定义在每个文件中都可见，无需 `use`
+    pub prelude: Vec<FileId>,
+    /// 这个 DefMap 所属的 crate
+    pub krate: CrateId,
+    /// 内建函数。它们与 `items` 共用 DefId 的编号，但不在 `items` 中，也不属于任何文件
+    pub builtins: HashMap<DefId, BuiltinDef>,
+}
+
+/// 一个名字在某个作用域中解析到的东西
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// 一个顶层定义
+    Def(DefId),
+    /// 通过 `use` 引入的模块
+    Module(FileId),
 }
 
 impl DefMap {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// 把本 crate 内的 DefId 提升为全局唯一的 GlobalDefId
+    pub fn global_id(&self, def_id: DefId) -> GlobalDefId {
+        GlobalDefId { krate: self.krate, local: def_id }
+    }
+
+    /// 合并多个 crate 的 DefMap。不同 crate 的定义即使本地 DefId 相同也不会冲突。
+    pub fn merge<'a>(def_maps: impl IntoIterator<Item = &'a DefMap>) -> HashMap<GlobalDefId, &'a ItemDef> {
+        def_maps
+            .into_iter()
+            .flat_map(|def_map| {
+                def_map.items.iter().map(|(def_id, item_def)| (def_map.global_id(*def_id), item_def))
+            })
+            .collect()
+    }
+
+    /// 所有测试函数，按 DefId 升序排列，供测试运行器使用
+    pub fn test_functions(&self) -> Vec<DefId> {
+        self.iter()
+            .filter(|(_, item_def)| item_def.is_test)
+            .map(|(def_id, _)| def_id)
+            .collect()
+    }
+
+    /// 定义所在的文件
+    pub fn file_of(&self, def_id: DefId) -> Option<FileId> {
+        self.items.get(&def_id).map(|item_def| item_def.file_id)
+    }
+
+    /// crate 根文件
+    pub fn crate_root(&self) -> Option<FileId> {
+        self.root
+    }
+
+    /// 在 `file_id` 的作用域中解析一个名字。
+    /// 优先级：本文件的定义 > 显式导入 > prelude，所以 prelude 中的名字可以被遮蔽。
+    pub fn resolve_name(&self, file_id: FileId, name: Symbol) -> Option<Binding> {
+        let file_defs = self.files.get(&file_id)?;
+
+        let local = file_defs.items.iter()
+            .find(|def_id| self.items.get(def_id).is_some_and(|item_def| item_def.name == name));
+        if let Some(def_id) = local {
+            return Some(Binding::Def(*def_id));
+        }
+
+        if let Some(module) = file_defs.imports.get(&name) {
+            return Some(Binding::Module(*module));
+        }
+
+        let prelude = self.prelude.iter()
+            .filter_map(|prelude_file| self.files.get(prelude_file))
+            .flat_map(|prelude_defs| prelude_defs.exports.iter())
+            .find(|def_id| self.items.get(def_id).is_some_and(|item_def| item_def.name == name));
+        if let Some(def_id) = prelude {
+            return Some(Binding::Def(*def_id));
+        }
+
+        self.builtin(name).map(Binding::Def)
+    }
+
+    /// 在 `file_id` 中解析一个限定路径，例如 `utils::helper`。
+    /// 除最后一段外，每一段都必须是（上一段所在模块中）导入的模块；最后一段是那个模块导出的定义。
+    /// 只有一段时等同于 `resolve_name`。
+    pub fn resolve_qualified(&self, file_id: FileId, path: &[Symbol]) -> Option<DefId> {
+        let (last, modules) = path.split_last()?;
+        if modules.is_empty() {
+            return match self.resolve_name(file_id, *last)? {
+                Binding::Def(def_id) => Some(def_id),
+                Binding::Module(_) => None,
+            };
+        }
+
+        let mut scope = file_id;
+        for segment in modules {
+            scope = *self.files.get(&scope)?.imports.get(segment)?;
+        }
+        self.files.get(&scope)?.exports.iter()
+            .copied()
+            .find(|def_id| self.items.get(def_id).is_some_and(|item_def| item_def.name == *last))
+    }
+
+    /// 按名字查找内建函数
+    pub fn builtin(&self, name: Symbol) -> Option<DefId> {
+        self.builtins.iter()
+            .filter(|(_, builtin)| builtin.name == name)
+            .map(|(def_id, _)| *def_id)
+            .min_by_key(|def_id| def_id.0)
+    }
+
+    /// 按 DefId 升序遍历所有定义。
+    /// `items` 是 HashMap，直接遍历的顺序不确定；日志、快照和序列化都应该用这个方法。
+    pub fn iter(&self) -> impl Iterator<Item = (DefId, &ItemDef)> {
+        let mut defs: Vec<(DefId, &ItemDef)> = self.items
+            .iter()
+            .map(|(def_id, item_def)| (*def_id, item_def))
+            .collect();
+        defs.sort_by_key(|(def_id, _)| def_id.0);
+        defs.into_iter()
+    }
+
+    /// 检查 DefMap 的内部一致性，供工具和测试在构造之后断言。
+    /// 返回所有发现的问题，而不是在第一个问题处停下。
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        for (def_id, item_def) in self.iter() {
+            if item_def.def_id != def_id {
+                problems.push(format!(
+                    "item stored under DefId({}) claims DefId({})",
+                    def_id.0, item_def.def_id.0
+                ));
+            }
+            let in_own_file = self.files
+                .get(&item_def.file_id)
+                .is_some_and(|file_defs| file_defs.items.contains(&def_id));
+            if !in_own_file {
+                problems.push(format!(
+                    "DefId({}) is missing from the fragment of its file {}",
+                    def_id.0, item_def.file_id
+                ));
+            }
+        }
+
+        let mut owners: HashMap<DefId, FileId> = HashMap::new();
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by_key(|(file_id, _)| **file_id);
+        for (file_id, file_defs) in files {
+            for def_id in &file_defs.items {
+                if !self.items.contains_key(def_id) {
+                    problems.push(format!(
+                        "file {} lists DefId({}) which is not in the map",
+                        file_id, def_id.0
+                    ));
+                }
+                if let Some(other) = owners.insert(*def_id, *file_id) {
+                    problems.push(format!(
+                        "DefId({}) is listed by both file {} and file {}",
+                        def_id.0, other, file_id
+                    ));
+                }
+            }
+            for def_id in &file_defs.exports {
+                if !file_defs.items.contains(def_id) {
+                    problems.push(format!(
+                        "file {} exports DefId({}) which it does not define",
+                        file_id, def_id.0
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// 按名字和种类查找一个顶层定义。
+    /// 目前还没有作用域的概念，所以是在整个 crate 中查找。
+    pub fn lookup(&self, name: Symbol, kind: ItemKind) -> Option<DefId> {
+        self.items
+            .values()
+            .filter(|item_def| item_def.name == name && item_def.kind == kind)
+            .map(|item_def| item_def.def_id)
+            .min_by_key(|def_id| def_id.0)
+    }
+
+    /// 生成一份确定性的文本快照，用于和 golden 文件做比对。
+    /// 每个定义一行，按 DefId 升序排列，例如 `fn main @ main.ny`。
+    pub fn to_golden_string<DB: ?Sized + AnalyzerDb>(&self, db: &DB) -> String {
+        let mut out = String::new();
+        for (_, item_def) in self.iter() {
+            out.push_str(&format!(
+                "{} {} @ {}\n",
+                item_def.kind.keyword(),
+                db.lookup_symbol(item_def.name),
+                db.file_name(item_def.file_id),
+            ));
+        }
+        out
+    }
 }
 
-/// 一个简单的 DefId 分配器
+/// 按文件缓存 `DefMap::resolve_qualified` 的结果。
+/// 同一个文件中反复出现的限定名（例如多次调用 `utils::helper`）只需要完整解析一次。
+/// 文件被重新分析后必须调用 `invalidate_file`；其他文件变化可能影响任何路径，此时应当 `clear`。
 #[derive(Debug, Default)]
+pub struct QualifiedCache {
+    entries: HashMap<FileId, HashMap<Vec<Symbol>, Option<DefId>>>,
+    misses: usize,
+}
+
+impl QualifiedCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resolve(&mut self, def_map: &DefMap, file_id: FileId, path: &[Symbol]) -> Option<DefId> {
+        let file_entries = self.entries.entry(file_id).or_default();
+        if let Some(cached) = file_entries.get(path) {
+            return *cached;
+        }
+        self.misses += 1;
+        let resolved = def_map.resolve_qualified(file_id, path);
+        file_entries.insert(path.to_vec(), resolved);
+        resolved
+    }
+
+    pub fn invalidate_file(&mut self, file_id: FileId) {
+        self.entries.remove(&file_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 没有命中缓存、需要完整解析的次数
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+fn content_hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 一个简单的 DefId 分配器
+#[derive(Debug, Clone, Default)]
 pub struct DefIdAllocator {
     counter: u32,
 }
 
 impl DefIdAllocator {
     pub fn new() -> Self { Self::default() }
+    /// 从某个 DefId 之后继续分配，用于在已有的 DefMap 上恢复工作
+    pub fn starting_after(def_map: &DefMap) -> Self {
+        let counter = def_map.items.keys()
+            .chain(def_map.builtins.keys())
+            .map(|def_id| def_id.0 + 1)
+            .max()
+            .unwrap_or(0);
+        Self { counter }
+    }
     pub fn new_def_id(&mut self) -> DefId {
         let id = self.counter;
         self.counter += 1;
@@ -54,11 +405,183 @@ impl DefIdAllocator {
     }
 }
 
+/// 在不经过源码的情况下逐个构造 DefMap，供需要合成定义的工具使用
+/// （例如生成的代码或 prelude）。合成的定义没有 AST 节点。
+#[derive(Debug, Default)]
+pub struct DefMapBuilder {
+    id_allocator: DefIdAllocator,
+    def_map: DefMap,
+}
+
+impl DefMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_function(&mut self, name: Symbol, file_id: FileId, span: Span) -> DefId {
+        self.add_item(name, ItemKind::Function, file_id, span)
+    }
+
+    pub fn add_struct(&mut self, name: Symbol, file_id: FileId, span: Span) -> DefId {
+        self.add_item(name, ItemKind::Struct, file_id, span)
+    }
+
+    pub fn add_item(&mut self, name: Symbol, kind: ItemKind, file_id: FileId, span: Span) -> DefId {
+        let def_id = self.id_allocator.new_def_id();
+        self.def_map.items.insert(def_id, ItemDef {
+            def_id,
+            name,
+            kind,
+            file_id,
+            span,
+            ast_node: None,
+            is_test: false,
+        });
+        let file_defs = self.def_map.files.entry(file_id).or_default();
+        file_defs.items.push(def_id);
+        file_defs.exports.push(def_id);
+        def_id
+    }
+
+    pub fn build(self) -> DefMap {
+        self.def_map
+    }
+}
+
+/// 待处理文件的工作队列。
+/// 每个文件最多入队一次，避免菱形依赖图把同一个文件反复压进队列。
+#[derive(Debug, Default)]
+pub(crate) struct Worklist {
+    queue: VecDeque<FileId>,
+    queued: HashSet<FileId>,
+}
+
+impl Worklist {
+    /// `done` 中的文件视为已经处理过，之后对它们的 `push` 会被忽略
+    pub(crate) fn new(done: impl IntoIterator<Item = FileId>) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            queued: done.into_iter().collect(),
+        }
+    }
+
+    /// 入队一个文件；如果它已经入过队，返回 `false`
+    pub(crate) fn push(&mut self, file_id: FileId) -> bool {
+        if !self.queued.insert(file_id) {
+            return false;
+        }
+        self.queue.push_back(file_id);
+        true
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<FileId> {
+        self.queue.pop_front()
+    }
+}
+
+/// 从入口文件出发、沿着 `use` 能到达的所有文件，即真正属于这个 crate 的文件。
+/// 构建工具可以用它忽略目录中的无关文件。
+/// 被导入但加载失败的文件同样属于 crate：它们没有片段，但出现在导入它们的文件的 `imports` 中。
+pub fn reachable_files<DB: ?Sized + AnalyzerDb>(db: &DB, entry_file: FileId) -> HashSet<FileId> {
+    let (def_map, _) = Resolver::new(db).collect_defs_crate(entry_file)
+        .expect("collection without a cancellation token cannot be cancelled");
+    let imported = def_map.files.values().flat_map(|file_defs| file_defs.imports.values().copied());
+    std::iter::once(entry_file)
+        .chain(def_map.files.keys().copied())
+        .chain(imported)
+        .collect()
+}
+
+/// 一个文件的传递导入闭包
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportClosure {
+    /// 文件本身以及它传递导入的所有文件，依赖在前、依赖者在后
+    pub order: Vec<FileId>,
+    /// 遍历时发现的成环的导入边 `(importer, imported)`；存在时 `order` 只是尽力而为的顺序
+    pub cycles: Vec<(FileId, FileId)>,
+}
+
+/// 计算 `file_id` 的传递导入闭包，按拓扑序排列（依赖在前），用于打包或单文件编译。
+pub fn import_closure<DB: ?Sized + AnalyzerDb>(db: &DB, file_id: FileId) -> ImportClosure {
+    let def_map = Resolver::new(db).collect_defs_crate(file_id)
+        .expect("collection without a cancellation token cannot be cancelled");
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark { Visiting, Done }
+
+    // 按 FileId 排序，保证输出稳定
+    let sorted_imports = |file_id: FileId| -> Vec<FileId> {
+        let mut imports: Vec<FileId> = def_map.files.get(&file_id)
+            .map(|file_defs| file_defs.imports.values().copied().collect())
+            .unwrap_or_default();
+        imports.sort();
+        imports.dedup();
+        imports
+    };
+
+    // 用显式的栈做深度优先遍历，很长的导入链也不会耗尽调用栈。
+    // 栈中每一项是 (文件, 它的导入, 下一个要访问的导入的下标)
+    let mut closure = ImportClosure::default();
+    let mut marks: HashMap<FileId, Mark> = HashMap::new();
+    let mut stack = vec![(file_id, sorted_imports(file_id), 0)];
+    marks.insert(file_id, Mark::Visiting);
+
+    while let Some((current, imports, next)) = stack.last_mut() {
+        let current = *current;
+        let Some(imported) = imports.get(*next).copied() else {
+            marks.insert(current, Mark::Done);
+            closure.order.push(current);
+            stack.pop();
+            continue;
+        };
+        *next += 1;
+        match marks.get(&imported) {
+            None => {
+                marks.insert(imported, Mark::Visiting);
+                stack.push((imported, sorted_imports(imported), 0));
+            }
+            Some(Mark::Visiting) => closure.cycles.push((current, imported)),
+            Some(Mark::Done) => {}
+        }
+    }
+    closure
+}
+
+/// 一次分析最多处理的文件数的默认值。
+/// 超过这个数量通常意味着 `resolve_module` 出了问题（例如不断返回新的 FileId）。
+pub const DEFAULT_MAX_FILES: usize = 10_000;
+
+/// Resolver 状态的快照，用于回滚一次试探性的收集（例如“如果加上这个 import 会怎样？”）。
+#[derive(Debug, Clone)]
+pub struct ResolverSnapshot {
+    id_allocator: DefIdAllocator,
+    def_map: DefMap,
+    diagnostics: Vec<Diagnostic>,
+    unresolved_names: Vec<(FileId, Span, Symbol)>,
+    failed_files: HashSet<FileId>,
+}
+
 /// Resolver 是我们的“图书管理员”，负责扫描代码并建立 DefMap。
 pub struct Resolver<'db, DB: ?Sized + AnalyzerDb> {
     db: &'db DB,
     id_allocator: DefIdAllocator,
     def_map: DefMap,
+    diagnostics: Vec<Diagnostic>,
+    max_files: usize,
+    cancel: Option<CancellationToken>,
+    /// 开启性能分析时，记录每个文件的收集耗时
+    file_timings: Option<HashMap<FileId, Duration>>,
+    /// 解析失败的名字，供 IDE 的快速修复使用
+    unresolved_names: Vec<(FileId, Span, Symbol)>,
+    /// 为 `false` 时只分析单个文件：不沿着 `use` 继续收集，也不报告解析失败的导入
+    follow_imports: bool,
+    /// `update_file` 期间，旧片段中可以被复用的 DefId，按 (文件, 名字, 种类) 匹配。
+    /// 带上文件是为了让更新中新导入的文件不会拿走被更新文件的 id
+    reusable_ids: HashMap<(FileId, Symbol, ItemKind), VecDeque<DefId>>,
+    /// 本次 `collect_crate` 是否已经报告过文件数上限
+    file_limit_reported: bool,
+    /// 加载失败的文件。它们没有片段，记录下来以免之后的 `collect_from` 再次入队、重复报告
+    failed_files: HashSet<FileId>,
 }
 
 impl<'db, DB: ?Sized + AnalyzerDb> Resolver<'db, DB> {
@@ -67,91 +590,440 @@ impl<'db, DB: ?Sized + AnalyzerDb> Resolver<'db, DB> {
             db,
             id_allocator: DefIdAllocator::new(),
             def_map: DefMap::new(),
+            diagnostics: Vec::new(),
+            max_files: DEFAULT_MAX_FILES,
+            cancel: None,
+            file_timings: None,
+            unresolved_names: Vec::new(),
+            follow_imports: true,
+            reusable_ids: HashMap::new(),
+            file_limit_reported: false,
+            failed_files: HashSet::new(),
         }
     }
 
+    /// 在一个已有的 DefMap 上恢复 Resolver，以便进行增量更新。
+    /// 新分配的 DefId 不会与已有的冲突。
+    pub fn with_def_map(db: &'db DB, def_map: DefMap) -> Self {
+        Self {
+            db,
+            id_allocator: DefIdAllocator::starting_after(&def_map),
+            def_map,
+            diagnostics: Vec::new(),
+            max_files: DEFAULT_MAX_FILES,
+            cancel: None,
+            file_timings: None,
+            unresolved_names: Vec::new(),
+            follow_imports: true,
+            reusable_ids: HashMap::new(),
+            file_limit_reported: false,
+            failed_files: HashSet::new(),
+        }
+    }
+
+    /// 设置最多处理的文件数，超过后收集会停止并报告错误
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// 设置正在收集的 crate 的编号，默认是 `CrateId(0)`
+    pub fn with_crate_id(mut self, krate: CrateId) -> Self {
+        self.def_map.krate = krate;
+        self
+    }
+
+    /// 加入一个 prelude 模块，它导出的定义在每个文件中都隐式可见
+    pub fn with_prelude(mut self, prelude_file: FileId) -> Self {
+        self.def_map.prelude.push(prelude_file);
+        self
+    }
+
+    /// 注入一个内建函数（例如 `print`），它在每个文件中都可见，无需 `use`
+    pub fn with_builtin(mut self, name: &str, signature: BuiltinSignature) -> Self {
+        let name = self.db.intern_string(name);
+        let def_id = self.id_allocator.new_def_id();
+        self.def_map.builtins.insert(def_id, BuiltinDef { name, signature });
+        self
+    }
+
+    /// 设置取消令牌。每处理一个文件之前都会检查它
+    pub fn with_cancellation(mut self, token: &CancellationToken) -> Self {
+        self.cancel = Some(token.clone());
+        self
+    }
+
+    /// 开启每个文件的耗时记录，结果用 `take_file_timings` 取出
+    pub fn with_profiling(mut self) -> Self {
+        self.file_timings = Some(HashMap::new());
+        self
+    }
+
     /// 这是“定义收集”的入口点。
-    /// 它将从一个入口文件开始，递归地遍历整个 crate，并返回完整的 DefMap。
-    pub fn collect_defs_crate(mut self, entry_file: FileId) -> DefMap {
-        let mut worklist: VecDeque<FileId> = VecDeque::new();
-        let mut visited: HashSet<FileId> = HashSet::new();
+    /// 它将从一个入口文件开始，递归地遍历整个 crate，返回 DefMap 以及收集过程中的诊断。
+    /// 有文件加载失败时，DefMap 包含其余文件的部分结果，失败记录在诊断中。
+    /// 如果中途被取消，返回 `Err(Cancelled)`，部分收集的结果被丢弃。
+    pub fn collect_defs_crate(mut self, entry_file: FileId) -> Result<(DefMap, Vec<Diagnostic>), Cancelled> {
+        self.collect_crate(entry_file)?;
+        Ok(self.finish())
+    }
+
+    /// 与 `collect_defs_crate` 相同，但不消耗 Resolver，之后可以用 `finish` 取出诊断。
+    /// 入口文件被记录为 crate 根，即使它位于某个子目录中。
+    #[cfg_attr(feature = "trace", tracing::instrument(level = "debug", skip(self)))]
+    pub fn collect_crate(&mut self, entry_file: FileId) -> Result<(), Cancelled> {
+        self.def_map.root.get_or_insert(entry_file);
+        self.file_limit_reported = false;
+        for prelude_file in self.def_map.prelude.clone() {
+            self.collect_from(prelude_file)?;
+        }
+        self.collect_from(entry_file)
+    }
+
+    /// 当前 crate 根文件；还没有收集过 crate 时为 `None`
+    pub fn crate_root(&self) -> Option<FileId> {
+        self.def_map.root
+    }
+
+    /// 只收集单个文件中的定义，不跟随 `use` 导入。
+    /// 适合快速 lint 和测试；跨文件的引用在这个模式下不会被当作错误。
+    pub fn collect_defs_file(mut self, file_id: FileId) -> Result<DefMap, Cancelled> {
+        self.follow_imports = false;
+        self.collect_from(file_id)?;
+        Ok(self.def_map)
+    }
 
-        worklist.push_back(entry_file);
+    /// 文件内容变化后，重新收集这个文件，并原子地替换它的定义片段。
+    /// 其他文件的片段（以及它们的 DefId）保持不变；新引入的依赖文件也会被收集。
+    ///
+    /// 仍然存在的定义（名字和种类都相同）保留原来的 DefId，
+    /// 这样其他文件持有的引用在可能的情况下依然有效；被删除的定义从 DefMap 中消失。
+    #[cfg_attr(feature = "trace", tracing::instrument(level = "debug", skip(self)))]
+    pub fn update_file(&mut self, file_id: FileId) -> Result<(), Cancelled> {
+        // 内容逐字节相同（例如编辑器中没有改动的保存）时什么都不用做
+        let unchanged = self.def_map.files.get(&file_id)
+            .and_then(|file_defs| file_defs.source_hash)
+            .is_some_and(|hash| hash == content_hash(&self.db.source_text(file_id)));
+        if unchanged {
+            return Ok(());
+        }
+
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+        // 之前加载失败的文件被显式更新时重新尝试
+        self.failed_files.remove(&file_id);
+
+        let old = self.def_map.files.remove(&file_id);
+        let mut old_items = Vec::new();
+        if let Some(old) = &old {
+            for def_id in &old.items {
+                if let Some(item_def) = self.def_map.items.remove(def_id) {
+                    self.reusable_ids
+                        .entry((file_id, item_def.name, item_def.kind))
+                        .or_default()
+                        .push_back(*def_id);
+                    old_items.push(item_def);
+                }
+            }
+        }
+        let result = self.collect_from(file_id);
+        self.reusable_ids.clear();
+
+        // 在重新收集这个文件之前就被取消了：放回旧的片段，不让它的定义丢失
+        if result.is_err() && !self.def_map.files.contains_key(&file_id) {
+            if let Some(old) = old {
+                self.def_map.files.insert(file_id, old);
+            }
+            for item_def in old_items {
+                self.def_map.items.insert(item_def.def_id, item_def);
+            }
+        }
+        result
+    }
+
+    /// 为一个新收集到的定义分配 DefId，优先复用 `update_file` 留下的、同一文件中同名同种类的旧 DefId
+    fn alloc_def_id(&mut self, file_id: FileId, name: Symbol, kind: ItemKind) -> DefId {
+        self.reusable_ids
+            .get_mut(&(file_id, name, kind))
+            .and_then(|ids| ids.pop_front())
+            .unwrap_or_else(|| self.id_allocator.new_def_id())
+    }
+
+    /// 当前已经收集到的 DefMap
+    pub fn def_map(&self) -> &DefMap {
+        &self.def_map
+    }
+
+    /// 记录当前的收集状态
+    pub fn snapshot(&self) -> ResolverSnapshot {
+        ResolverSnapshot {
+            id_allocator: self.id_allocator.clone(),
+            def_map: self.def_map.clone(),
+            diagnostics: self.diagnostics.clone(),
+            unresolved_names: self.unresolved_names.clone(),
+            failed_files: self.failed_files.clone(),
+        }
+    }
+
+    /// 回到 `snapshot` 时的状态，丢弃之后所有的收集结果
+    pub fn restore(&mut self, snapshot: ResolverSnapshot) {
+        self.id_allocator = snapshot.id_allocator;
+        self.def_map = snapshot.def_map;
+        self.diagnostics = snapshot.diagnostics;
+        self.unresolved_names = snapshot.unresolved_names;
+        self.failed_files = snapshot.failed_files;
+    }
+
+    /// 取出目前为止记录的每个文件的收集耗时；未开启性能分析时为空
+    pub fn take_file_timings(&mut self) -> HashMap<FileId, Duration> {
+        self.file_timings.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// 取出目前为止所有解析失败的名字
+    pub fn take_unresolved_names(&mut self) -> Vec<(FileId, Span, Symbol)> {
+        std::mem::take(&mut self.unresolved_names)
+    }
+
+    /// 取出当前的 DefMap
+    pub fn into_def_map(self) -> DefMap {
+        self.def_map
+    }
+
+    /// 取出当前的 DefMap 以及收集过程中产生的诊断
+    pub fn finish(self) -> (DefMap, Vec<Diagnostic>) {
+        (self.def_map, self.diagnostics)
+    }
+
+    /// 从 `start` 开始沿着 `use` 遍历，收集所有尚未收集过的文件。
+    fn collect_from(&mut self, start: FileId) -> Result<(), Cancelled> {
+        // 已经有片段的文件和加载失败的文件视为处理过，不会再次入队
+        let done = self.def_map.files.keys().chain(&self.failed_files).copied();
+        let mut worklist = Worklist::new(done);
+
+        worklist.push(start);
         
-        while let Some(file_id) = worklist.pop_front() {
-            if !visited.insert(file_id) {
-                // 如果文件已经被访问过（insert 返回 false），就跳过
-                continue;
+        while let Some(file_id) = worklist.pop() {
+            if self.is_cancelled() {
+                return Err(Cancelled);
             }
 
+            if self.def_map.files.len() >= self.max_files {
+                // prelude 和入口文件各走一遍，上限只报告一次
+                if !self.file_limit_reported {
+                    self.diagnostics.push(Diagnostic::error(format!(
+                        "analysis aborted: more than {} files reachable from the crate root",
+                        self.max_files
+                    )));
+                    self.file_limit_reported = true;
+                }
+                break;
+            }
+
+            let started = Instant::now();
+            #[cfg(feature = "trace")]
+            let file_span = tracing::debug_span!("collect_file", file_id, items = tracing::field::Empty).entered();
+
             // 1. 通过 Trait 向“数据库”查询这个文件的 AST
-            let ast = self.db.ast(file_id);
+            let Some(ast) = self.db.ast(file_id) else {
+                // 保留已经收集到的部分结果，只记录错误
+                let mut diagnostic = Diagnostic::error(format!(
+                    "failed to load `{}`",
+                    self.db.file_name(file_id)
+                ));
+                diagnostic.file_id = Some(file_id);
+                self.diagnostics.push(diagnostic);
+                self.failed_files.insert(file_id);
+                continue;
+            };
 
             // 2. 调用我们的单文件分析函数，进行定义收集
-            self.collect_defs_in_module(&ast);
+            let mut file_defs = self.collect_defs_in_module(file_id, &ast);
 
             // 3. 扫描 `use` 语句，发现新的依赖文件
             for item in &ast.items {
                 if let AstItem::Use(use_stmt) = item {
-                    self.discover_deps_in_tree(file_id, &use_stmt.tree, &mut worklist);
+                    self.discover_deps_in_tree(file_id, &use_stmt.tree, &mut file_defs, &mut worklist);
                 }
             }
+
+            #[cfg(feature = "trace")]
+            file_span.record("items", file_defs.items.len());
+            self.def_map.files.insert(file_id, file_defs);
+
+            if let Some(file_timings) = &mut self.file_timings {
+                file_timings.insert(file_id, started.elapsed());
+            }
         }
-        
-        self.def_map // 返回最终的成果
+
+        Ok(())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|token| token.is_cancelled())
     }
     
-    /// (这是一个私有辅助函数) 负责扫描单个模块的 AST，并将定义添加到 DefMap。
-    fn collect_defs_in_module(&mut self, module_ast: &ast::Module) {
+    /// (这是一个私有辅助函数) 负责扫描单个模块的 AST，将定义添加到 DefMap，
+    /// 并返回这个文件的定义片段。
+    fn collect_defs_in_module(&mut self, file_id: FileId, module_ast: &ast::Module) -> FileDefs {
+        let mut file_defs = FileDefs {
+            source_hash: Some(content_hash(&self.db.source_text(file_id))),
+            ..FileDefs::default()
+        };
         for item in &module_ast.items {
             match &item {
                 AstItem::Function(func_def) => {
-                    let def_id = self.id_allocator.new_def_id();
-                    
                     // --- 核心修复点 ---
                     // 通过 db 接口调用 interner 服务，将 &str 转换为 Symbol
                     let name_symbol = self.db.intern_string(&func_def.name.lexeme);
+                    let def_id = self.alloc_def_id(file_id, name_symbol, ItemKind::Function);
 
                     let item_def = ItemDef {
                         def_id,
                         name: name_symbol, // 现在类型匹配了！
                         kind: ItemKind::Function,
-                        ast_node: Arc::new(item.clone()),
+                        file_id,
+                        span: func_def.name.span,
+                        ast_node: Some(Arc::new(item.clone())),
+                        is_test: is_test_function_name(&func_def.name.lexeme),
                     };
                     self.def_map.items.insert(def_id, item_def);
+                    file_defs.items.push(def_id);
                 }
                 AstItem::Struct(struct_def) => {
-                    let def_id = self.id_allocator.new_def_id();
-                    
                     // --- 核心修复点 ---
                     let name_symbol = self.db.intern_string(&struct_def.name.lexeme);
+                    let def_id = self.alloc_def_id(file_id, name_symbol, ItemKind::Struct);
                     
                     let item_def = ItemDef {
                         def_id,
                         name: name_symbol, // 类型匹配！
                         kind: ItemKind::Struct,
-                        ast_node: Arc::new(item.clone()),
+                        file_id,
+                        span: struct_def.name.span,
+                        ast_node: Some(Arc::new(item.clone())),
+                        is_test: false,
                     };
                     self.def_map.items.insert(def_id, item_def);
+                    file_defs.items.push(def_id);
                 }
                 AstItem::Use(_) => { /* ... */ }
             }
         }
+        file_defs.exports = file_defs.items.clone();
+        file_defs
+    }
+
+    /// 解析一个 `use` 路径。以 `crate` 开头的路径锚定在 crate 根文件，
+    /// 其余路径交给“数据库”相对于当前文件解析。
+    pub(crate) fn resolve_use_path(&self, anchor_file: FileId, path: &ast::Path) -> ModuleResolution {
+        match path.segments.first() {
+            Some(first) if first.lexeme == "crate" => {
+                let Some(root) = self.def_map.root else {
+                    return ModuleResolution::NotFound { segment: 0 };
+                };
+                if path.segments.len() == 1 {
+                    return ModuleResolution::Found(root);
+                }
+                self.db.resolve_module_from(root, path, 1)
+            }
+            _ => self.db.resolve_module_detailed(anchor_file, path),
+        }
+    }
+
+    /// 为一条无法解析的 `use` 路径给出与失败原因对应的诊断
+    fn report_failed_import(&mut self, anchor_file: FileId, path: &ast::Path, resolution: ModuleResolution) {
+        let path_str = path.segments.iter()
+            .map(|s| s.lexeme.as_str())
+            .collect::<Vec<_>>()
+            .join("::");
+        let Some(last) = path.segments.last() else {
+            // 空路径没有可以指向的位置
+            self.diagnostics.push(Diagnostic {
+                file_id: Some(anchor_file),
+                ..Diagnostic::error("malformed import path").with_code("malformed-import")
+            });
+            return;
+        };
+
+        let (code, message) = match resolution {
+            ModuleResolution::Found(_) => return,
+            ModuleResolution::NotFound { segment } => {
+                // 只标出出错的那一段，而不是整条路径
+                let failed = path.segments.get(segment).unwrap_or(last);
+                let name = self.db.intern_string(&failed.lexeme);
+                self.unresolved_names.push((anchor_file, failed.span, name));
+                let message = match segment.checked_sub(1).and_then(|parent| path.segments.get(parent)) {
+                    Some(parent) if segment < path.segments.len() - 1 => format!(
+                        "unresolved import `{}`: could not find `{}` in `{}`",
+                        path_str, failed.lexeme, parent.lexeme
+                    ),
+                    _ => format!("unresolved import `{}`", path_str),
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(message)
+                        .with_code("unresolved-import")
+                        .at(anchor_file, failed.span),
+                );
+                return;
+            }
+            ModuleResolution::Ambiguous(candidates) => {
+                let candidates: Vec<String> = candidates.iter()
+                    .map(|file_id| self.db.file_name(*file_id))
+                    .collect();
+                ("ambiguous-import", format!("import `{}` is ambiguous: found {}", path_str, candidates.join(", ")))
+            }
+            ModuleResolution::Malformed => ("malformed-import", format!("malformed import path `{}`", path_str)),
+        };
+        self.diagnostics.push(Diagnostic::error(message).with_code(code).at(anchor_file, last.span));
     }
 
     /// (新的私有辅助函数) 递归地遍历 UseTree，找出所有需要解析的模块路径
-    fn discover_deps_in_tree(&self, anchor_file: FileId, tree: &ast::UseTree, worklist: &mut VecDeque<FileId>) {
+    fn discover_deps_in_tree(&mut self, anchor_file: FileId, tree: &ast::UseTree, file_defs: &mut FileDefs, worklist: &mut Worklist) {
         match tree {
             ast::UseTree::Simple { path, .. } => {
                 // 通过 Trait，让“数据库”去解析这个 use 路径
-                if let Some(resolved_file_id) = self.db.resolve_module(anchor_file, path) {
-                    worklist.push_back(resolved_file_id);
+                #[cfg(feature = "trace")]
+                let span = tracing::trace_span!("resolve_module", anchor_file, resolved = tracing::field::Empty).entered();
+                let resolved_file_id = match self.resolve_use_path(anchor_file, path) {
+                    ModuleResolution::Found(file_id) => file_id,
+                    // 单文件模式下容忍无法解析的导入
+                    _ if !self.follow_imports => return,
+                    resolution => {
+                        self.report_failed_import(anchor_file, path, resolution);
+                        return;
+                    }
+                };
+                #[cfg(feature = "trace")]
+                span.record("resolved", resolved_file_id);
+                // 以路径的最后一段作为引入的名字
+                if let Some(last) = path.segments.last() {
+                    let name = self.db.intern_string(&last.lexeme);
+                    match file_defs.imports.get(&name) {
+                        // 完全相同的重复导入：静默合并
+                        Some(existing) if *existing == resolved_file_id => {}
+                        // 同一个名字指向不同的目标：真正的冲突
+                        Some(_) => self.diagnostics.push(
+                            Diagnostic::error(format!(
+                                "the name `{}` is imported multiple times with different targets",
+                                last.lexeme
+                            ))
+                            .with_code("conflicting-import")
+                            .at(anchor_file, last.span),
+                        ),
+                        None => {
+                            file_defs.imports.insert(name, resolved_file_id);
+                        }
+                    }
+                }
+                if self.follow_imports {
+                    worklist.push(resolved_file_id);
                 }
             },
             ast::UseTree::Group { items } => {
                 // 递归地处理分组中的每一项
                 for item in items {
-                    self.discover_deps_in_tree(anchor_file, item, worklist);
+                    self.discover_deps_in_tree(anchor_file, item, file_defs, worklist);
                 }
             },
             ast::UseTree::Wildcard { .. } => {
@@ -0,0 +1,34 @@
+// analyzer/src/suggest.rs
+
+/// 超过这个编辑距离的候选不会被当作 “did you mean” 建议。
+pub const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// 两个字符串之间的 Levenshtein 编辑距离（按字符计算）。
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // 只保留上一行，节省内存
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// 在候选名字中找出与 `name` 最接近的一个。
+/// 只有编辑距离不超过 `MAX_SUGGESTION_DISTANCE` 时才返回；距离相同时取字典序最小的，保证结果稳定。
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
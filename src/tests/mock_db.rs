@@ -0,0 +1,210 @@
+use super::super::*;
+use crate::db::{ModuleResolution, Overlay, ResolveCache};
+use ast::{Module as AstModule, Path as AstPath};
+use nyanc_core::{FileId, Symbol};
+use parser::Parser;
+use reporter::DiagnosticsEngine;
+use lexer::Lexer;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// --- 步骤 1: 在测试模块内部，定义一个自给自足的 Interner ---
+// 这个 Interner 的定义和实现与 driver 中的完全一样，但它只属于这个测试模块。
+#[derive(Debug, Default)]
+pub struct TestInterner {
+    map: HashMap<String, Symbol>,
+    vec: Vec<String>,
+}
+
+impl TestInterner {
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(symbol) = self.map.get(s) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.vec.len() as u32);
+        let s = s.to_string();
+        self.vec.push(s.clone());
+        self.map.insert(s, symbol);
+        symbol
+    }
+
+    /// 根据 Symbol 查找回原始的字符串切片。
+    pub fn lookup(&self, symbol: Symbol) -> &str {
+        // Symbol 的 u32 值，就是它在 vec 中的索引
+        &self.vec[symbol.0 as usize]
+    }
+}
+
+// --- 步骤 2: 更新 MockDb，让它使用我们本地的 TestInterner ---
+#[derive(Default)]
+pub struct MockDb {
+    pub interner: RefCell<TestInterner>, // <-- 使用 TestInterner
+    pub sources: HashMap<FileId, Arc<String>>,
+    pub paths: HashMap<String, FileId>,
+    pub ast_cache: RefCell<HashMap<FileId, Arc<AstModule>>>,
+    pub resolve_cache: ResolveCache,
+    /// 每次加载 AST 时调用，测试可以借此在分析中途做点什么（比如取消）
+    pub on_ast: Option<Box<dyn Fn(FileId)>>,
+    /// 模拟解析失败的文件
+    pub parse_failures: HashSet<FileId>,
+    /// 只存在于内存中的文件（模拟编辑器中未保存的缓冲区）
+    pub overlay: Overlay,
+    /// `intern_string` 被调用的次数
+    pub intern_calls: Cell<usize>,
+    next_file_id: FileId,
+}
+
+impl AnalyzerDb for MockDb {
+    fn ast(&self, file_id: FileId) -> Option<Arc<AstModule>> {
+        if let Some(on_ast) = &self.on_ast {
+            on_ast(file_id);
+        }
+        if self.parse_failures.contains(&file_id) {
+            return None;
+        }
+        if let Some(ast) = self.ast_cache.borrow().get(&file_id) {
+            return Some(ast.clone());
+        }
+
+        let source_text = self.overlay.source(file_id)
+            .or_else(|| self.sources.get(&file_id).cloned())?;
+        let diagnostics = DiagnosticsEngine::default(); // 测试中暂时忽略解析错误
+        let lexer = Lexer::new(&source_text, file_id, &diagnostics);
+        let mut parser = Parser::new(lexer, &diagnostics);
+        let ast = Arc::new(parser.parse());
+        
+        self.ast_cache.borrow_mut().insert(file_id, ast.clone());
+        Some(ast)
+    }
+    
+    fn source_text(&self, file_id: FileId) -> Arc<String> {
+        self.overlay.source(file_id)
+            .or_else(|| self.sources.get(&file_id).cloned())
+            .unwrap_or_default()
+    }
+
+    fn resolve_module(&self, anchor_file: FileId, path: &AstPath) -> Option<FileId> {
+        self.resolve_module_detailed(anchor_file, path).file()
+    }
+
+    fn resolve_module_detailed(&self, anchor_file: FileId, path: &AstPath) -> ModuleResolution {
+        self.resolve_module_from(anchor_file, path, 0)
+    }
+
+    fn resolve_module_from(&self, anchor_file: FileId, path: &AstPath, start: usize) -> ModuleResolution {
+        self.resolve_cache.get_or_resolve_from(anchor_file, path, start, || {
+            self.lookup_module(anchor_file, path, start)
+        })
+    }
+
+    fn module_candidates(&self, anchor_file: FileId, name: &str) -> Vec<FileId> {
+        let anchor_name = self.file_name(anchor_file);
+        let path = match anchor_name.rsplit_once('/') {
+            Some((dir, _)) => format!("{}/{}", dir, name),
+            None => name.to_string(),
+        };
+
+        // --- 核心修复点 ---
+        // 模拟真实的模块解析行为：尝试添加 .ny 后缀
+        [format!("{}.ny", path), format!("{}/mod.ny", path)]
+            .iter()
+            // 覆盖层优先于“磁盘”
+            .filter_map(|candidate| {
+                self.overlay.file_id(candidate)
+                    .or_else(|| self.paths.get(candidate).copied())
+            })
+            .collect()
+    }
+
+    fn clear_resolve_cache(&self) {
+        self.resolve_cache.clear();
+    }
+    
+    fn intern_string(&self, s: &str) -> Symbol {
+        self.intern_calls.set(self.intern_calls.get() + 1);
+        self.interner.borrow_mut().intern(s)
+    }
+
+    fn lookup_symbol(&self, symbol: Symbol) -> String {
+        self.interner.borrow().lookup(symbol).to_string()
+    }
+
+    fn file_name(&self, file_id: FileId) -> String {
+        if let Some(path) = self.overlay.path(file_id) {
+            return path.to_string();
+        }
+        self.paths.iter()
+            .find(|(_, id)| **id == file_id)
+            .map(|(path, _)| path.clone())
+            .unwrap_or_else(|| format!("<file {}>", file_id))
+    }
+}
+
+
+impl MockDb {
+    pub fn add_file(&mut self, path: &str, source: &str) -> FileId {
+        let file_id = self.fresh_file_id();
+        self.sources.insert(file_id, Arc::new(source.to_string()));
+        self.paths.insert(path.to_string(), file_id);
+        file_id
+    }
+
+    /// 添加一个只存在于覆盖层中的文件，它没有对应的“磁盘”文件
+    pub fn add_overlay_file(&mut self, path: &str, source: &str) -> FileId {
+        let file_id = self.fresh_file_id();
+        self.overlay.insert(path, file_id, source);
+        file_id
+    }
+
+    fn fresh_file_id(&mut self) -> FileId {
+        let file_id = self.next_file_id;
+        self.next_file_id += 1;
+        file_id
+    }
+
+    // 模拟模块解析：路径相对于 `anchor_file` 所在的目录，
+    // `a::b` 可以对应 `a/b.ny` 或 `a/b/mod.ny`，两者同时存在时有歧义。
+    // 只看从第 `start` 段开始的部分，`NotFound` 的下标也相对于这一部分
+    pub fn lookup_module(&self, anchor_file: FileId, path: &AstPath, start: usize) -> ModuleResolution {
+        let segments = &path.segments[start..];
+        if segments.is_empty() || segments.iter().any(|s| s.lexeme.is_empty()) {
+            return ModuleResolution::Malformed;
+        }
+        let name = segments.iter()
+            .map(|s| s.lexeme.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let candidates = self.module_candidates(anchor_file, &name);
+        match candidates.as_slice() {
+            [] => ModuleResolution::NotFound { segment: self.failing_segment(anchor_file, path, start) },
+            [file_id] => ModuleResolution::Found(*file_id),
+            _ => ModuleResolution::Ambiguous(candidates),
+        }
+    }
+
+    // 第一个不存在对应目录的前缀段；所有前缀都存在时是最后一段（文件本身不存在）
+    fn failing_segment(&self, anchor_file: FileId, path: &AstPath, start: usize) -> usize {
+        let segments = &path.segments[start..];
+        let anchor_name = self.file_name(anchor_file);
+        let mut dir = anchor_name.rsplit_once('/')
+            .map(|(dir, _)| dir.to_string())
+            .unwrap_or_default();
+        let last = segments.len() - 1;
+        for (index, segment) in segments[..last].iter().enumerate() {
+            dir = if dir.is_empty() { segment.lexeme.clone() } else { format!("{}/{}", dir, segment.lexeme) };
+            let prefix = format!("{}/", dir);
+            if !self.paths.keys().any(|path| path.starts_with(&prefix)) {
+                return index;
+            }
+        }
+        last
+    }
+
+    /// 模拟编辑器修改了文件内容：替换源码并让 AST 缓存失效
+    pub fn set_source(&mut self, file_id: FileId, source: &str) {
+        self.sources.insert(file_id, Arc::new(source.to_string()));
+        self.ast_cache.borrow_mut().remove(&file_id);
+    }
+}
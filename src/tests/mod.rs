@@ -1 +1,13 @@
-mod test_resolver;
\ No newline at end of file
+mod mock_db;
+mod alloc_counter;
+mod test_resolver;
+mod test_check;
+mod test_index;
+mod test_suggest;
+mod test_lint;
+mod test_diff;
+mod test_pass;
+#[cfg(feature = "lsp")]
+mod test_lsp;
+#[cfg(feature = "trace")]
+mod test_trace;
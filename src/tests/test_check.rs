@@ -0,0 +1,426 @@
+use super::mock_db::MockDb;
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::check::{check_crate, check_crate_subset, check_crate_with, CheckOptions};
+use crate::db::AnalyzerDb;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::resolver::NamespaceMode;
+use nyanc_core::{FileId, Symbol};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[test]
+fn test_severity_filtering() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use missing
+        fun main() {}
+    "#);
+
+    let mut result = check_crate(&db, main_fid);
+    // analyzer 目前还不会产生警告，这里手动加入一条，模拟 unused-import
+    result.diagnostics.push(Diagnostic::warning("unused import `utils`"));
+
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["unresolved import `missing`"]);
+
+    let all: Vec<_> = result.all().map(|diag| diag.severity).collect();
+    assert_eq!(all, vec![Severity::Error, Severity::Warning]);
+    assert!(result.has_errors());
+
+    let at_least_warning: Vec<_> = result.at_least(Severity::Warning).map(|diag| diag.severity).collect();
+    assert_eq!(at_least_warning, vec![Severity::Error, Severity::Warning]);
+    assert_eq!(result.at_least(Severity::Error).count(), 1);
+}
+
+#[test]
+fn test_severity_ordering() {
+    assert!(Severity::Warning.at_least(Severity::Hint));
+    assert!(Severity::Warning.at_least(Severity::Warning));
+    assert!(!Severity::Warning.at_least(Severity::Error));
+    assert!(Severity::Error.at_least(Severity::Hint));
+    assert!(!Severity::Hint.at_least(Severity::Warning));
+}
+
+#[test]
+fn test_cleared_resolve_cache_re_resolves() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+
+    // 第一次解析失败，失败的结果也被缓存了
+    assert!(check_crate(&db, main_fid).has_errors());
+    assert_eq!(db.resolve_cache.len(), 1);
+
+    // 文件出现后，缓存仍然返回旧的失败结果
+    db.add_file("utils.ny", "fun helper() {}");
+    assert!(check_crate(&db, main_fid).has_errors());
+
+    // 清除缓存后重新解析成功
+    db.clear_resolve_cache();
+    let result = check_crate(&db, main_fid);
+    assert!(!result.has_errors());
+    assert_eq!(result.def_map.items.len(), 2);
+}
+
+#[test]
+fn test_cancellation_aborts_collection() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    // 加载 main.ny 的时候“用户按下了新的按键”
+    let token = CancellationToken::new();
+    let trigger = token.clone();
+    db.on_ast = Some(Box::new(move |file_id| {
+        if file_id == main_fid {
+            trigger.cancel();
+        }
+    }));
+
+    let options = CheckOptions { cancel: Some(token.clone()), ..Default::default() };
+    assert_eq!(check_crate_with(&db, main_fid, &options).unwrap_err(), Cancelled);
+
+    // utils.ny 从未被加载
+    assert!(!db.ast_cache.borrow().contains_key(&utils_fid));
+}
+
+#[test]
+fn test_duplicate_imports() {
+    let mut db = MockDb::default();
+    let benign = db.add_file("main.ny", r#"
+        use utils
+        use utils
+        fun main() {}
+    "#);
+    let conflicting = db.add_file("other.ny", r#"
+        use utils
+        use lib::utils
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+    db.add_file("lib/utils.ny", "fun other_helper() {}");
+
+    // 相同的重复导入不产生诊断
+    assert_eq!(check_crate(&db, benign).all().count(), 0);
+
+    // 同名但目标不同的导入被诊断为冲突
+    let result = check_crate(&db, conflicting);
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["the name `utils` is imported multiple times with different targets"]);
+    assert_eq!(result.errors().next().unwrap().code, Some("conflicting-import"));
+}
+
+#[test]
+fn test_phase_timings_when_profiling() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    // 默认不记录耗时
+    assert!(check_crate(&db, main_fid).timings.is_none());
+
+    let options = CheckOptions { profile: true, ..Default::default() };
+    let result = check_crate_with(&db, main_fid, &options).unwrap();
+    let timings = result.timings.expect("profiling was enabled");
+
+    assert_eq!(timings.per_file.len(), 2);
+    assert!(timings.per_file.contains_key(&main_fid));
+    assert!(timings.per_file.contains_key(&utils_fid));
+    let per_file_total: std::time::Duration = timings.per_file.values().sum();
+    assert!(timings.collection >= per_file_total);
+}
+
+#[test]
+fn test_partial_result_when_entry_fails_to_parse() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "fun main( {");
+    db.parse_failures.insert(main_fid);
+
+    let result = check_crate(&db, main_fid);
+
+    let errors: Vec<_> = result.errors().collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "failed to load `main.ny`");
+    assert_eq!(errors[0].file_id, Some(main_fid));
+    // 空的但有效的 DefMap
+    assert!(result.def_map.items.is_empty());
+}
+
+#[test]
+fn test_partial_result_keeps_collected_files() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+    db.parse_failures.insert(utils_fid);
+
+    let result = check_crate(&db, main_fid);
+    assert_eq!(result.errors().count(), 1);
+    assert_eq!(result.def_map.items.len(), 1);
+}
+
+#[test]
+fn test_unresolved_names_are_collected() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        use helpr
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    let result = check_crate(&db, main_fid);
+    let unresolved = result.unresolved_names();
+
+    assert_eq!(unresolved.len(), 1);
+    let (file_id, _span, name) = unresolved[0];
+    assert_eq!(file_id, main_fid);
+    assert_eq!(db.lookup_symbol(name), "helpr");
+}
+
+#[test]
+fn test_multiple_main_functions_across_files() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use other
+        fun main() {}
+    "#);
+    db.add_file("other.ny", "fun main() {}");
+
+    let result = check_crate(&db, main_fid);
+    let errors: Vec<_> = result.errors().collect();
+
+    // 只有一条 crate 级别的错误，列出了所有位置
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "multiple `main` functions found: main.ny, other.ny");
+    assert_eq!(errors[0].file_id, None);
+}
+
+#[test]
+fn test_diagnostics_grouped_by_file() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use other
+        use missing
+        fun main() {}
+    "#);
+    let other_fid = db.add_file("other.ny", r#"
+        use nowhere
+        fun main() {}
+    "#);
+
+    let result = check_crate(&db, main_fid);
+    let by_file = result.diagnostics_by_file();
+
+    assert_eq!(by_file.len(), 2);
+    assert_eq!(by_file[&main_fid][0].message, "unresolved import `missing`");
+    assert_eq!(by_file[&other_fid][0].message, "unresolved import `nowhere`");
+
+    // 重复的 `main` 是 crate 级别的，单独列出
+    let crate_level: Vec<_> = result.crate_diagnostics().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(crate_level, vec!["multiple `main` functions found: main.ny, other.ny"]);
+}
+
+#[test]
+fn test_import_conflicts_depend_on_namespace_mode() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun utils() {}
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    // 类型和值分开：导入的模块 `utils` 与函数 `utils` 不冲突
+    assert!(!check_crate(&db, main_fid).has_errors());
+
+    // 统一命名空间：冲突
+    let options = CheckOptions { namespaces: NamespaceMode::Unified, ..Default::default() };
+    let result = check_crate_with(&db, main_fid, &options).unwrap();
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["`utils` is defined here but also imported in this file"]);
+}
+
+#[test]
+fn test_import_conflicts_with_same_namespace() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        struct utils {}
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    // 结构体和模块同在类型命名空间，即使分开模式下也冲突
+    let result = check_crate(&db, main_fid);
+    let codes: Vec<_> = result.errors().map(|diag| diag.code).collect();
+    assert_eq!(codes, vec![Some("import-conflict")]);
+}
+
+#[test]
+fn test_ambiguous_module_lists_candidates() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+    db.add_file("utils/mod.ny", "fun helper() {}");
+
+    let result = check_crate(&db, main_fid);
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["import `utils` is ambiguous: found utils.ny, utils/mod.ny"]);
+    // 歧义不是拼写错误，不应出现在未解析的名字里
+    assert!(result.unresolved_names().is_empty());
+}
+
+#[test]
+fn test_mod_file_resolves_directory_module() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils/mod.ny", "fun helper() {}");
+
+    let result = check_crate(&db, main_fid);
+    assert!(!result.has_errors());
+    assert_eq!(result.def_map.items.len(), 2);
+}
+
+#[test]
+fn test_unresolved_import_points_at_failing_segment() {
+    let mut db = MockDb::default();
+    let source = r#"
+        use a::b::c
+        fun main() {}
+    "#;
+    let main_fid = db.add_file("main.ny", source);
+    db.add_file("a/other.ny", "fun helper() {}");
+
+    let result = check_crate(&db, main_fid);
+    let errors: Vec<_> = result.errors().collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "unresolved import `a::b::c`: could not find `b` in `a`");
+
+    // 下划线只覆盖 `b` 这一段
+    let span = errors[0].span.unwrap();
+    assert_eq!(span.start, source.find("b::c").unwrap());
+
+    let unresolved = result.unresolved_names();
+    assert_eq!(db.lookup_symbol(unresolved[0].2), "b");
+}
+
+#[test]
+fn test_resolve_cache_interns_each_path_once() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        use shapes
+        fun main() {}
+    "#);
+    db.add_file("shapes.ny", r#"
+        use utils
+        struct Point {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    for _ in 0..3 {
+        db.clear_resolve_cache();
+        assert!(!check_crate(&db, main_fid).has_errors());
+    }
+
+    // 每个 (文件, 路径) 一条缓存，但 `utils` 只被驻留（分配）了一次
+    assert_eq!(db.resolve_cache.len(), 3);
+    assert_eq!(db.resolve_cache.interned_paths(), 2);
+}
+
+#[test]
+fn test_check_subset_reports_only_allowed_files() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", r#"
+        use missing
+        fun helper() {}
+    "#);
+
+    // 完整分析时 utils.ny 中有一个错误
+    assert!(check_crate(&db, main_fid).has_errors());
+
+    let allow = HashSet::from([main_fid]);
+    let result = check_crate_subset(&db, main_fid, &allow);
+    assert_eq!(result.all().count(), 0);
+    assert!(result.unresolved_names().is_empty());
+    // utils.ny 的定义仍然被收集，供 main.ny 解析使用
+    assert_eq!(result.def_map.items.len(), 2);
+}
+
+/// 只提供文件列表的 db：`resolve_module` 总是失败，解析完全依赖
+/// `module_candidates` 和 `resolve_module_detailed` 的默认实现
+struct ListingDb(MockDb);
+
+impl AnalyzerDb for ListingDb {
+    fn ast(&self, file_id: FileId) -> Option<Arc<ast::Module>> {
+        self.0.ast(file_id)
+    }
+
+    fn source_text(&self, file_id: FileId) -> Arc<String> {
+        self.0.source_text(file_id)
+    }
+
+    fn resolve_module(&self, _anchor_file: FileId, _path: &ast::Path) -> Option<FileId> {
+        None
+    }
+
+    fn module_candidates(&self, anchor_file: FileId, name: &str) -> Vec<FileId> {
+        self.0.module_candidates(anchor_file, name)
+    }
+
+    fn intern_string(&self, s: &str) -> Symbol {
+        self.0.intern_string(s)
+    }
+
+    fn lookup_symbol(&self, symbol: Symbol) -> String {
+        self.0.lookup_symbol(symbol)
+    }
+
+    fn file_name(&self, file_id: FileId) -> String {
+        self.0.file_name(file_id)
+    }
+}
+
+#[test]
+fn test_module_candidates_drive_default_resolution() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("src/main.ny", r#"
+        use geo
+        use shapes
+        fun main() {}
+    "#);
+    let geo_fid = db.add_file("src/geo.ny", "fun area() {}");
+    let geo_mod_fid = db.add_file("src/geo/mod.ny", "fun perimeter() {}");
+    let shapes_fid = db.add_file("src/shapes/mod.ny", "struct Circle {}");
+
+    assert_eq!(db.module_candidates(main_fid, "geo"), vec![geo_fid, geo_mod_fid]);
+    assert_eq!(db.module_candidates(main_fid, "shapes"), vec![shapes_fid]);
+    assert!(db.module_candidates(main_fid, "missing").is_empty());
+
+    let db = ListingDb(db);
+    let result = check_crate(&db, main_fid);
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["import `geo` is ambiguous: found src/geo.ny, src/geo/mod.ny"]);
+    // 唯一的候选被选中
+    assert_eq!(result.def_map.files[&main_fid].imports[&db.intern_string("shapes")], shapes_fid);
+}
@@ -0,0 +1,58 @@
+use super::mock_db::MockDb;
+use crate::diff::diff;
+use crate::resolver::Resolver;
+
+#[test]
+fn test_diff_after_adding_a_function() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", r#"
+        struct Point {}
+        fun helper() {}
+    "#);
+    let (old, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    // 在 main.ny 的最前面加一个函数，所有 DefId 都会跟着变
+    db.set_source(main_fid, r#"
+        use utils
+        fun setup() {}
+        fun main() {}
+    "#);
+    let (new, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let changes = diff(&old, &new, &db);
+    assert_eq!(changes.added, vec!["setup".to_string()]);
+    assert!(changes.removed.is_empty());
+
+    assert!(diff(&new, &new, &db).is_empty());
+}
+
+#[test]
+fn test_diff_keeps_struct_and_fn_with_the_same_name() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        struct Point {}
+    "#);
+    let (old, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    // 加一个与结构体同名的函数，它们在不同的命名空间中
+    db.set_source(main_fid, r#"
+        fun main() {}
+        struct Point {}
+        fun Point() {}
+    "#);
+    let (new, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let changes = diff(&old, &new, &db);
+    assert_eq!(changes.added, vec!["Point".to_string()]);
+    assert!(changes.removed.is_empty());
+
+    // 反过来删掉函数，结构体仍然在
+    let changes = diff(&new, &old, &db);
+    assert_eq!(changes.removed, vec!["Point".to_string()]);
+    assert!(changes.added.is_empty());
+}
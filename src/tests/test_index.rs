@@ -0,0 +1,148 @@
+use super::mock_db::MockDb;
+use crate::db::AnalyzerDb;
+use crate::index::{export_symbol_index, kind_at, module_path, qualified_name, resolve_def_path, SemanticKind};
+use crate::line_index::LineIndex;
+use crate::resolver::{ItemKind, Resolver};
+
+#[test]
+fn test_export_symbol_index() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "use utils\nfun main() {}\n");
+    db.add_file("utils.ny", "fun helper() {}\n\nstruct Point {}\n");
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let index = export_symbol_index(&db, &def_map);
+
+    let point = index.iter().find(|entry| entry.name == "utils::Point").unwrap();
+    assert_eq!(point.kind, "struct");
+    assert_eq!(point.file, "utils.ny");
+    assert_eq!(point.line, 3);
+
+    let json = serde_json::to_string(&index).unwrap();
+    assert!(json.contains(r#"{"name":"utils::Point","kind":"struct","file":"utils.ny","line":3}"#));
+}
+
+#[test]
+fn test_resolve_def_path() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("src/main.ny", r#"
+        use utils
+        use geo::shapes
+        fun main() {}
+    "#);
+    db.add_file("src/utils.ny", "fun helper() {}");
+    db.add_file("src/geo/shapes.ny", "struct Circle {}");
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let lookup = |name| def_map.lookup(db.intern_string(name), ItemKind::Function);
+
+    assert_eq!(resolve_def_path(&db, &def_map, "utils::helper"), lookup("helper"));
+    assert_eq!(resolve_def_path(&db, &def_map, "crate::utils::helper"), lookup("helper"));
+    assert_eq!(resolve_def_path(&db, &def_map, "main"), lookup("main"));
+    assert_eq!(
+        resolve_def_path(&db, &def_map, "geo::shapes::Circle"),
+        def_map.lookup(db.intern_string("Circle"), ItemKind::Struct),
+    );
+    assert!(lookup("helper").is_some());
+    assert_eq!(resolve_def_path(&db, &def_map, "nope::x"), None);
+}
+
+#[test]
+fn test_line_index_line_col() {
+    let index = LineIndex::new("fun main() {}\nuse utils\n");
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line_col(0), (0, 0));
+    assert_eq!(index.line_col(4), (0, 4));
+    // 换行符属于它所在的那一行
+    assert_eq!(index.line_col(13), (0, 13));
+    assert_eq!(index.line_col(14), (1, 0));
+    assert_eq!(index.line_col(18), (1, 4));
+    // 超出末尾按末尾处理
+    assert_eq!(index.line_col(1000), (2, 0));
+}
+
+#[test]
+fn test_line_index_utf16_columns() {
+    // `名字` 每个字 3 个字节、1 个 UTF-16 单元；`😺` 4 个字节、2 个 UTF-16 单元
+    let text = "fun main() {}\n// 名字 😺 x\n";
+    let index = LineIndex::new(text);
+    let x = text.find('x').unwrap();
+    assert_eq!(index.line_col(x), (1, 15));
+    assert_eq!(index.line_col_utf16(x), (1, 9));
+    // 前面几行的多字节字符不影响后面的行，ASCII 行两种列相同
+    assert_eq!(index.line_col_utf16(4), (0, 4));
+    assert_eq!(index.line_col_utf16(text.len()), (2, 0));
+}
+
+#[test]
+fn test_line_index_utf16_offset_inside_char() {
+    // 偏移 1 和 2 落在 `名` 的中间，按它的开头处理
+    let index = LineIndex::new("名 x");
+    assert_eq!(index.line_col_utf16(1), (0, 0));
+    assert_eq!(index.line_col_utf16(2), (0, 0));
+    assert_eq!(index.line_col_utf16(3), (0, 1));
+    assert_eq!(index.line_col_utf16(4), (0, 2));
+}
+
+#[test]
+fn test_kind_at_position() {
+    let mut db = MockDb::default();
+    let source = r#"
+        use utils
+        struct Point {}
+        fun main() {}
+    "#;
+    let main_fid = db.add_file("main.ny", source);
+    db.add_file("utils.ny", "fun helper() {}");
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let offset_of = |text: &str| source.find(text).unwrap();
+    assert_eq!(kind_at(&db, &def_map, main_fid, offset_of("Point")), Some(SemanticKind::Item(ItemKind::Struct)));
+    assert_eq!(kind_at(&db, &def_map, main_fid, offset_of("main")), Some(SemanticKind::Item(ItemKind::Function)));
+    assert_eq!(kind_at(&db, &def_map, main_fid, offset_of("utils")), Some(SemanticKind::Module));
+    // `struct` 关键字不是标识符
+    assert_eq!(kind_at(&db, &def_map, main_fid, offset_of("struct")), None);
+}
+
+#[test]
+fn test_qualified_names_round_trip() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("src/main.ny", r#"
+        use utils
+        use geo::shapes
+        fun main() {}
+    "#);
+    db.add_file("src/utils.ny", "fun helper() {}");
+    db.add_file("src/geo/shapes.ny", "struct Circle {}");
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let names: Vec<_> = def_map.iter().map(|(_, item_def)| qualified_name(&db, &def_map, item_def)).collect();
+    // 根文件中的定义没有模块前缀，其他文件相对于 `src/`
+    assert_eq!(names, vec!["main", "utils::helper", "geo::shapes::Circle"]);
+
+    for (def_id, item_def) in def_map.iter() {
+        let name = qualified_name(&db, &def_map, item_def);
+        assert_eq!(resolve_def_path(&db, &def_map, &name), Some(def_id));
+    }
+}
+
+#[test]
+fn test_directory_module_paths() {
+    assert_eq!(module_path("geo/mod.ny"), "geo");
+    assert_eq!(module_path("mod.ny"), "");
+    assert_eq!(module_path("geo/model.ny"), "geo::model");
+    assert_eq!(module_path("geo/shapes/mod.ny"), "geo::shapes");
+
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils/mod.ny", "fun helper() {}");
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let helper = def_map.lookup(db.intern_string("helper"), ItemKind::Function);
+    let names: Vec<_> = export_symbol_index(&db, &def_map).into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, vec!["main", "utils::helper"]);
+    assert_eq!(resolve_def_path(&db, &def_map, "utils::helper"), helper);
+}
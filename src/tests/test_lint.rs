@@ -0,0 +1,50 @@
+use super::mock_db::MockDb;
+use crate::check::{check_crate, check_crate_with, CheckOptions};
+use crate::lint::{CaseStyle, NamingConfig};
+
+#[test]
+fn test_case_styles() {
+    assert!(CaseStyle::SnakeCase.matches("helper_fn2"));
+    assert!(CaseStyle::SnakeCase.matches("_unused"));
+    assert!(!CaseStyle::SnakeCase.matches("MyFunc"));
+    assert!(CaseStyle::PascalCase.matches("Point3D"));
+    assert!(!CaseStyle::PascalCase.matches("point"));
+    assert!(!CaseStyle::PascalCase.matches("My_Point"));
+}
+
+#[test]
+fn test_naming_lint_is_opt_in() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        fun MyFunc() {}
+        struct Point {}
+    "#);
+
+    // 默认不开启
+    assert_eq!(check_crate(&db, main_fid).all().count(), 0);
+
+    let options = CheckOptions { naming: Some(NamingConfig::default()), ..Default::default() };
+    let result = check_crate_with(&db, main_fid, &options).unwrap();
+    let warnings: Vec<_> = result.all().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(warnings, vec!["function `MyFunc` should have a snake_case name"]);
+    assert!(!result.has_errors());
+}
+
+#[test]
+fn test_deny_warnings_promotes_lint_warnings() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        fun MyFunc() {}
+    "#);
+
+    let options = CheckOptions { naming: Some(NamingConfig::default()), ..Default::default() };
+    assert!(!check_crate_with(&db, main_fid, &options).unwrap().has_errors());
+
+    let strict = CheckOptions { deny_warnings: true, ..options };
+    let result = check_crate_with(&db, main_fid, &strict).unwrap();
+    assert!(result.has_errors());
+    let errors: Vec<_> = result.errors().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(errors, vec!["function `MyFunc` should have a snake_case name"]);
+}
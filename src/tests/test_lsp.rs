@@ -0,0 +1,43 @@
+use super::mock_db::MockDb;
+use crate::check::check_crate;
+use crate::lsp::{LspPosition, LspRange};
+
+#[test]
+fn test_unresolved_import_converts_to_lsp_diagnostic() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "use missing\nfun main() {}\n");
+
+    let result = check_crate(&db, main_fid);
+    let by_file = result.to_lsp_diagnostics(&db);
+    let diagnostics = &by_file[&main_fid];
+    assert_eq!(diagnostics.len(), 1);
+
+    let diag = &diagnostics[0];
+    assert_eq!(diag.severity, 1);
+    assert_eq!(diag.code, Some("unresolved-import"));
+    assert_eq!(diag.message, "unresolved import `missing`");
+    assert_eq!(diag.range, LspRange {
+        start: LspPosition { line: 0, character: 4 },
+        end: LspPosition { line: 0, character: 11 },
+    });
+
+    let json = serde_json::to_value(diag).unwrap();
+    assert_eq!(json["range"]["start"]["character"], 4);
+    assert_eq!(json["code"], "unresolved-import");
+}
+
+#[test]
+fn test_lsp_columns_count_utf16_units() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "use missing\nfun main() {}\n");
+    let result = check_crate(&db, main_fid);
+
+    // 把 span 前面的 `use ` 换成同样 4 个字节的 `名 `：诊断的字节偏移不变，
+    // 但 `名` 只占 1 个 UTF-16 单元，列要相应地变小
+    db.set_source(main_fid, "名 missing\nfun main() {}\n");
+    let by_file = result.to_lsp_diagnostics(&db);
+    assert_eq!(by_file[&main_fid][0].range, LspRange {
+        start: LspPosition { line: 0, character: 2 },
+        end: LspPosition { line: 0, character: 9 },
+    });
+}
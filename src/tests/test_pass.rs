@@ -0,0 +1,56 @@
+use super::mock_db::MockDb;
+use crate::check::{check_crate_with_passes, CheckOptions};
+use crate::db::AnalyzerDb;
+use crate::diagnostics::Diagnostic;
+use crate::pass::{AnalysisPass, MultipleMainsPass, PassRegistry};
+use crate::resolver::DefMap;
+
+/// 为每个函数给出一条提示的测试用 pass
+struct ListFunctions;
+
+impl<DB: ?Sized + AnalyzerDb> AnalysisPass<DB> for ListFunctions {
+    fn name(&self) -> &'static str {
+        "list-functions"
+    }
+
+    fn run(&self, db: &DB, def_map: &DefMap) -> Vec<Diagnostic> {
+        def_map.iter()
+            .map(|(_, item_def)| Diagnostic::hint(format!("fn {}", db.lookup_symbol(item_def.name))))
+            .collect()
+    }
+}
+
+#[test]
+fn test_registry_runs_passes_in_order() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use other
+        fun main() {}
+    "#);
+    db.add_file("other.ny", "fun main() {}");
+
+    let mut passes = PassRegistry::new();
+    passes.register(ListFunctions).register(MultipleMainsPass);
+    assert_eq!(passes.names(), vec!["list-functions", "multiple-mains"]);
+
+    let result = check_crate_with_passes(&db, main_fid, &CheckOptions::default(), &passes).unwrap();
+    let messages: Vec<_> = result.all().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(messages, vec![
+        "fn main",
+        "fn main",
+        "multiple `main` functions found: main.ny, other.ny",
+    ]);
+}
+
+#[test]
+fn test_empty_registry_only_reports_resolution() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use other
+        fun main() {}
+    "#);
+    db.add_file("other.ny", "fun main() {}");
+
+    let result = check_crate_with_passes(&db, main_fid, &CheckOptions::default(), &PassRegistry::new()).unwrap();
+    assert_eq!(result.all().count(), 0);
+}
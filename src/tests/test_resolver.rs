@@ -1,98 +1,16 @@
 use super::super::*;
-use crate::resolver::ItemKind;
-use ast::{Module as AstModule, Path as AstPath};
-use nyanc_core::{FileId, Symbol};
+use super::alloc_counter::count_allocations;
+use super::mock_db::MockDb;
+use crate::resolver::{ItemDef, ItemKind, Worklist};
+use crate::ty::{BuiltinSignature, CallError};
+use hir::Type;
+use lexer::Lexer;
+use nyanc_core::{FileId, Span, Symbol};
 use parser::Parser;
 use reporter::DiagnosticsEngine;
-use lexer::Lexer;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::cell::Cell;
 use std::collections::HashSet;
-
-// --- 步骤 1: 在测试模块内部，定义一个自给自足的 Interner ---
-// 这个 Interner 的定义和实现与 driver 中的完全一样，但它只属于这个测试模块。
-#[derive(Debug, Default)]
-struct TestInterner {
-    map: HashMap<String, Symbol>,
-    vec: Vec<String>,
-}
-
-impl TestInterner {
-    fn intern(&mut self, s: &str) -> Symbol {
-        if let Some(symbol) = self.map.get(s) {
-            return *symbol;
-        }
-        let symbol = Symbol(self.vec.len() as u32);
-        let s = s.to_string();
-        self.vec.push(s.clone());
-        self.map.insert(s, symbol);
-        symbol
-    }
-
-    /// 根据 Symbol 查找回原始的字符串切片。
-    fn lookup(&self, symbol: Symbol) -> &str {
-        // Symbol 的 u32 值，就是它在 vec 中的索引
-        &self.vec[symbol.0 as usize]
-    }
-}
-
-// --- 步骤 2: 更新 MockDb，让它使用我们本地的 TestInterner ---
-#[derive(Default)]
-struct MockDb {
-    interner: RefCell<TestInterner>, // <-- 使用 TestInterner
-    sources: HashMap<FileId, Arc<String>>,
-    paths: HashMap<String, FileId>,
-    ast_cache: RefCell<HashMap<FileId, Arc<AstModule>>>,
-}
-
-impl AnalyzerDb for MockDb {
-    fn ast(&self, file_id: FileId) -> Arc<AstModule> {
-        if let Some(ast) = self.ast_cache.borrow().get(&file_id) {
-            return ast.clone();
-        }
-
-        let source_text = self.sources.get(&file_id).unwrap().clone();
-        let diagnostics = DiagnosticsEngine::default(); // 测试中暂时忽略解析错误
-        let lexer = Lexer::new(&source_text, file_id, &diagnostics);
-        let mut parser = Parser::new(lexer, &diagnostics);
-        let ast = Arc::new(parser.parse());
-        
-        self.ast_cache.borrow_mut().insert(file_id, ast.clone());
-        ast
-    }
-    
-    // 模拟模块解析：只处理简单的文件名
-    fn resolve_module(&self, _anchor_file: FileId, path: &AstPath) -> Option<FileId> {
-        let path_str = path.segments.iter()
-            .map(|s| s.lexeme.as_str())
-            .collect::<Vec<_>>()
-            .join("/");
-        
-        // --- 核心修复点 ---
-        // 模拟真实的模块解析行为：尝试添加 .ny 后缀
-        let mut resolved_path = path_str;
-        if !resolved_path.ends_with(".ny") {
-             resolved_path.push_str(".ny");
-        }
-
-        self.paths.get(&resolved_path).copied()
-    }
-    
-    fn intern_string(&self, s: &str) -> Symbol {
-        self.interner.borrow_mut().intern(s)
-    }
-}
-
-
-impl MockDb {
-    fn add_file(&mut self, path: &str, source: &str) -> FileId {
-        let file_id: FileId = self.sources.len();
-        self.sources.insert(file_id, Arc::new(source.to_string()));
-        self.paths.insert(path.to_string(), file_id);
-        file_id
-    }
-}
+use std::sync::Arc;
 
 // --- 步骤 3: 我们的测试用例现在可以无依赖地运行了 ---
 #[test]
@@ -114,7 +32,7 @@ fn test_multi_module_def_collection() {
 
     // 2. 执行 (Act): 运行我们的定义收集器
     let resolver = Resolver::new(&db);
-    let def_map = resolver.collect_defs_crate(main_fid);
+    let (def_map, _) = resolver.collect_defs_crate(main_fid).unwrap();
 
     // 3. 断言 (Assert): 检查结果是否符合预期
     
@@ -147,4 +65,785 @@ fn test_multi_module_def_collection() {
             unexpected_name => panic!("Unexpected item found: {:?}", unexpected_name),
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_def_map_golden_output() {
+    let mut db = MockDb::default();
+
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", r#"
+        struct Point {}
+        fun helper() {}
+    "#);
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    // 快照必须与 golden 文件逐字节一致
+    let expected = include_str!("golden/multi_module.golden");
+    assert_eq!(def_map.to_golden_string(&db), expected);
+}
+
+#[test]
+fn test_update_file_replaces_only_its_fragment() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", r#"
+        struct Point {}
+        fun helper() {}
+    "#);
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let main_defs_before = def_map.files[&main_fid].items.clone();
+    let utils_defs_before = def_map.files[&utils_fid].items.clone();
+    assert_eq!(def_map.files[&main_fid].imports.get(&db.intern_string("utils")), Some(&utils_fid));
+
+    // 编辑 utils.ny，并只重新收集这一个文件
+    db.set_source(utils_fid, r#"
+        struct Point {}
+        fun helper() {}
+        fun extra() {}
+    "#);
+    let mut resolver = Resolver::with_def_map(&db, def_map);
+    resolver.update_file(utils_fid).unwrap();
+    let def_map = resolver.into_def_map();
+
+    // main.ny 的片段原封不动
+    assert_eq!(def_map.files[&main_fid].items, main_defs_before);
+    // utils.ny 的片段被整体替换；仍然存在的定义保留原来的 DefId
+    let utils_defs_after = &def_map.files[&utils_fid].items;
+    assert_eq!(utils_defs_after.len(), 3);
+    assert_eq!(utils_defs_after[..2], utils_defs_before[..]);
+    assert_eq!(def_map.items.len(), 4);
+    assert_eq!(def_map.validate(), Ok(()));
+}
+
+fn id_of(db: &MockDb, def_map: &DefMap, name: &str, kind: ItemKind) -> Option<hir::DefId> {
+    def_map.lookup(db.intern_string(name), kind)
+}
+
+#[test]
+fn test_update_file_keeps_surviving_def_ids() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun first() {}
+        fun second() {}
+        struct Gone {}
+    "#);
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let first = id_of(&db, &def_map, "first", ItemKind::Function).unwrap();
+    let second = id_of(&db, &def_map, "second", ItemKind::Function).unwrap();
+    let gone = id_of(&db, &def_map, "Gone", ItemKind::Struct).unwrap();
+
+    // 在前面插入一个新函数，删除结构体，并调换已有函数的顺序
+    db.set_source(main_fid, r#"
+        fun added() {}
+        fun second() {}
+        fun first() {}
+    "#);
+    let mut resolver = Resolver::with_def_map(&db, def_map);
+    resolver.update_file(main_fid).unwrap();
+    let def_map = resolver.into_def_map();
+
+    assert_eq!(id_of(&db, &def_map, "first", ItemKind::Function), Some(first));
+    assert_eq!(id_of(&db, &def_map, "second", ItemKind::Function), Some(second));
+    let added = id_of(&db, &def_map, "added", ItemKind::Function).unwrap();
+    assert!(added != first && added != second && added != gone);
+    assert!(!def_map.items.contains_key(&gone));
+}
+
+/// 一个“坏掉的”数据库：每个文件都 `use next`，而 `next` 每次都被解析成一个全新的文件
+#[derive(Default)]
+struct EndlessDb {
+    inner: MockDb,
+    next_file: Cell<FileId>,
+}
+
+impl AnalyzerDb for EndlessDb {
+    fn ast(&self, file_id: FileId) -> Option<Arc<ast::Module>> {
+        let source_text = self.source_text(file_id);
+        let diagnostics = DiagnosticsEngine::default();
+        let lexer = Lexer::new(&source_text, file_id, &diagnostics);
+        let mut parser = Parser::new(lexer, &diagnostics);
+        Some(Arc::new(parser.parse()))
+    }
+
+    fn source_text(&self, _file_id: FileId) -> Arc<String> {
+        Arc::new("use next".to_string())
+    }
+
+    fn resolve_module(&self, _anchor_file: FileId, _path: &ast::Path) -> Option<FileId> {
+        let next = self.next_file.get() + 1;
+        self.next_file.set(next);
+        Some(next)
+    }
+
+    fn intern_string(&self, s: &str) -> Symbol {
+        self.inner.intern_string(s)
+    }
+
+    fn lookup_symbol(&self, symbol: Symbol) -> String {
+        self.inner.lookup_symbol(symbol)
+    }
+
+    fn file_name(&self, file_id: FileId) -> String {
+        format!("file{}.ny", file_id)
+    }
+}
+
+#[test]
+fn test_max_files_guard() {
+    let db = EndlessDb::default();
+
+    let mut resolver = Resolver::new(&db).with_max_files(16);
+    resolver.collect_crate(0).unwrap();
+    let (def_map, diagnostics) = resolver.finish();
+
+    assert_eq!(def_map.files.len(), 16);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("more than 16 files"));
+}
+
+#[test]
+fn test_max_files_reported_once_with_prelude() {
+    let db = EndlessDb::default();
+
+    // 上限在收集 prelude 时就达到了，入口文件那一遍不应再报告一次
+    let mut resolver = Resolver::new(&db).with_max_files(16).with_prelude(0);
+    resolver.collect_crate(100).unwrap();
+    let (def_map, diagnostics) = resolver.finish();
+
+    assert_eq!(def_map.files.len(), 16);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_def_map_iter_is_sorted() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+        fun second() {}
+    "#);
+    db.add_file("utils.ny", r#"
+        struct Point {}
+        fun helper() {}
+    "#);
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let ids: Vec<u32> = def_map.iter().map(|(def_id, _)| def_id.0).collect();
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+    for (def_id, item_def) in def_map.iter() {
+        assert_eq!(def_id, item_def.def_id);
+    }
+}
+
+#[test]
+fn test_def_map_builder() {
+    let db = MockDb::default();
+    let prelude_file: FileId = 0;
+
+    let mut builder = DefMapBuilder::new();
+    let print = builder.add_function(db.intern_string("print"), prelude_file, Span::default());
+    let string = builder.add_struct(db.intern_string("String"), prelude_file, Span::default());
+    let def_map = builder.build();
+
+    assert_eq!(def_map.items.len(), 2);
+    assert_eq!(def_map.lookup(db.intern_string("print"), ItemKind::Function), Some(print));
+    assert_eq!(def_map.lookup(db.intern_string("String"), ItemKind::Struct), Some(string));
+    assert!(def_map.items[&print].ast_node.is_none());
+    assert_eq!(def_map.files[&prelude_file].items, vec![print, string]);
+}
+
+#[test]
+fn test_worklist_enqueues_each_file_once() {
+    let mut worklist = Worklist::new([]);
+    let shared: FileId = 3;
+
+    // 三个文件都导入了同一个文件
+    assert!(worklist.push(shared));
+    assert!(!worklist.push(shared));
+    assert!(!worklist.push(shared));
+    assert_eq!(worklist.pop(), Some(shared));
+    assert_eq!(worklist.pop(), None);
+
+    // 出队之后也不会再次入队
+    assert!(!worklist.push(shared));
+    assert_eq!(worklist.pop(), None);
+}
+
+#[test]
+fn test_diamond_imports_collect_shared_file_once() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use a
+        use b
+        use c
+    "#);
+    db.add_file("a.ny", "use shared");
+    db.add_file("b.ny", "use shared");
+    db.add_file("c.ny", "use shared");
+    db.add_file("shared.ny", "fun helper() {}");
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    assert_eq!(def_map.files.len(), 5);
+    assert_eq!(def_map.items.len(), 1);
+}
+
+#[test]
+fn test_collect_single_file() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", r#"
+        use missing
+        struct Point {}
+        fun helper() {}
+    "#);
+
+    let def_map = Resolver::new(&db).collect_defs_file(utils_fid).unwrap();
+    assert_eq!(def_map.items.len(), 2);
+    assert_eq!(def_map.files.len(), 1);
+
+    // main.ny 从未被加载
+    assert!(!db.ast_cache.borrow().contains_key(&main_fid));
+}
+
+#[test]
+fn test_def_map_validate() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    let (mut def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    assert_eq!(def_map.validate(), Ok(()));
+
+    // 故意破坏：utils.ny 的片段里多出一个不存在的 DefId
+    def_map.files.get_mut(&utils_fid).unwrap().items.push(hir::DefId(42));
+    let problems = def_map.validate().unwrap_err();
+    assert_eq!(problems, vec![format!("file {} lists DefId(42) which is not in the map", utils_fid)]);
+}
+
+#[test]
+fn test_overlay_file_participates_in_resolution() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use generated
+        fun main() {}
+    "#);
+    // `generated.ny` 只存在于内存中
+    let generated_fid = db.add_overlay_file("generated.ny", "struct Generated {}");
+    assert!(!db.sources.contains_key(&generated_fid));
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let generated = def_map.lookup(db.intern_string("Generated"), ItemKind::Struct).unwrap();
+    assert_eq!(def_map.items[&generated].file_id, generated_fid);
+    assert_eq!(db.file_name(generated_fid), "generated.ny");
+}
+
+#[test]
+fn test_crate_paths_anchor_at_nested_root() {
+    let mut db = MockDb::default();
+    // 入口文件本身就在子目录里
+    let main_fid = db.add_file("src/main.ny", r#"
+        use geo::shapes
+        fun main() {}
+    "#);
+    db.add_file("src/geo/shapes.ny", r#"
+        use crate::utils
+        struct Circle {}
+    "#);
+    let utils_fid = db.add_file("src/utils.ny", "fun helper() {}");
+
+    let mut resolver = Resolver::new(&db);
+    resolver.collect_crate(main_fid).unwrap();
+    assert_eq!(resolver.crate_root(), Some(main_fid));
+    let (def_map, diagnostics) = resolver.finish();
+
+    assert!(diagnostics.is_empty());
+    assert_eq!(def_map.crate_root(), Some(main_fid));
+    assert!(def_map.files.contains_key(&utils_fid));
+    assert_eq!(def_map.items.len(), 3);
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "fun main() {}");
+    db.add_file("utils.ny", "fun helper() {}");
+
+    let mut resolver = Resolver::new(&db);
+    resolver.collect_crate(main_fid).unwrap();
+    let snapshot = resolver.snapshot();
+    drop(resolver);
+
+    // 试探：如果 main.ny 加上 `use utils` 会怎样？
+    db.set_source(main_fid, "use utils\nfun main() {}");
+    let def_map = {
+        let mut resolver = Resolver::new(&db);
+        resolver.restore(snapshot.clone());
+        resolver.update_file(main_fid).unwrap();
+        assert_eq!(resolver.def_map().items.len(), 2);
+
+        // 回滚
+        resolver.restore(snapshot);
+        resolver.into_def_map()
+    };
+    assert_eq!(def_map.items.len(), 1);
+    assert_eq!(def_map.validate(), Ok(()));
+}
+
+#[test]
+fn test_file_of() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let helper = id_of(&db, &def_map, "helper", ItemKind::Function).unwrap();
+
+    assert_eq!(def_map.file_of(helper), Some(utils_fid));
+    assert_eq!(db.file_name(def_map.file_of(helper).unwrap()), "utils.ny");
+    assert_eq!(def_map.file_of(hir::DefId(99)), None);
+}
+
+#[test]
+fn test_prelude_names_are_in_scope() {
+    let mut db = MockDb::default();
+    let prelude_fid = db.add_file("prelude.ny", r#"
+        struct Option {}
+        struct String {}
+    "#);
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        struct String {}
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    let (def_map, _) = Resolver::new(&db).with_prelude(prelude_fid).collect_defs_crate(main_fid).unwrap();
+
+    // 没有 `use` 也能看到 prelude 里的 `Option`
+    let option = id_of(&db, &def_map, "Option", ItemKind::Struct).unwrap();
+    assert_eq!(def_map.file_of(option), Some(prelude_fid));
+    assert_eq!(def_map.resolve_name(main_fid, db.intern_string("Option")), Some(Binding::Def(option)));
+    assert_eq!(def_map.resolve_name(utils_fid, db.intern_string("Option")), Some(Binding::Def(option)));
+
+    // 本地定义遮蔽 prelude
+    let Some(Binding::Def(string)) = def_map.resolve_name(main_fid, db.intern_string("String")) else {
+        panic!("`String` should resolve to a definition");
+    };
+    assert_eq!(def_map.file_of(string), Some(main_fid));
+
+    // 显式导入也在作用域中
+    assert_eq!(def_map.resolve_name(main_fid, db.intern_string("utils")), Some(Binding::Module(utils_fid)));
+    assert_eq!(def_map.resolve_name(main_fid, db.intern_string("Missing")), None);
+}
+
+#[test]
+fn test_reachable_files() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+    // 目录里的无关文件
+    db.add_file("stray.ny", "fun unused() {}");
+
+    let reachable = reachable_files(&db, main_fid);
+    assert_eq!(reachable, HashSet::from([main_fid, utils_fid]));
+}
+
+#[test]
+fn test_reachable_files_include_files_that_fail_to_load() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper( {");
+    db.parse_failures.insert(utils_fid);
+
+    // 坏掉的文件仍然属于 crate，并且与 `import_closure` 看到的是同一张图
+    let reachable = reachable_files(&db, main_fid);
+    assert_eq!(reachable, HashSet::from([main_fid, utils_fid]));
+    let closure: HashSet<_> = import_closure(&db, main_fid).order.into_iter().collect();
+    assert_eq!(reachable, closure);
+}
+
+#[test]
+fn test_global_def_ids_do_not_collide_across_crates() {
+    let mut db = MockDb::default();
+    let app_fid = db.add_file("app/main.ny", "fun main() {}");
+    let lib_fid = db.add_file("lib/lib.ny", "fun helper() {}");
+
+    let (app, _) = Resolver::new(&db).with_crate_id(CrateId(0)).collect_defs_crate(app_fid).unwrap();
+    let (lib, _) = Resolver::new(&db).with_crate_id(CrateId(1)).collect_defs_crate(lib_fid).unwrap();
+
+    // 两个 crate 的本地 DefId 都从 0 开始
+    assert!(app.items.contains_key(&hir::DefId(0)));
+    assert!(lib.items.contains_key(&hir::DefId(0)));
+
+    let merged = DefMap::merge([&app, &lib]);
+    assert_eq!(merged.len(), 2);
+    let main = merged[&app.global_id(hir::DefId(0))];
+    let helper = merged[&lib.global_id(hir::DefId(0))];
+    assert_eq!(db.lookup_symbol(main.name), "main");
+    assert_eq!(db.lookup_symbol(helper.name), "helper");
+}
+
+#[test]
+fn test_def_visitor_counts_kinds() {
+    #[derive(Default)]
+    struct Counter {
+        functions: Vec<u32>,
+        structs: usize,
+    }
+
+    impl DefVisitor for Counter {
+        fn visit_function(&mut self, def_id: hir::DefId, _item_def: &ItemDef) {
+            self.functions.push(def_id.0);
+        }
+        fn visit_struct(&mut self, _def_id: hir::DefId, _item_def: &ItemDef) {
+            self.structs += 1;
+        }
+    }
+
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", r#"
+        struct Point {}
+        fun helper() {}
+    "#);
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let mut counter = Counter::default();
+    def_map.walk(&mut counter);
+    // 按 DefId 顺序访问
+    assert_eq!(counter.functions, vec![0, 2]);
+    assert_eq!(counter.structs, 1);
+}
+
+#[test]
+fn test_import_closure_is_topologically_sorted() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+
+    let closure = import_closure(&db, main_fid);
+    assert_eq!(closure.order, vec![utils_fid, main_fid]);
+    assert!(closure.cycles.is_empty());
+}
+
+#[test]
+fn test_import_closure_notes_cycles() {
+    let mut db = MockDb::default();
+    let a_fid = db.add_file("a.ny", "use b");
+    let b_fid = db.add_file("b.ny", "use a");
+
+    let closure = import_closure(&db, a_fid);
+    assert_eq!(closure.order, vec![b_fid, a_fid]);
+    assert_eq!(closure.cycles, vec![(b_fid, a_fid)]);
+}
+
+#[test]
+fn test_test_functions_query() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        fun test_it_works() {}
+        fun tested() {}
+        struct test_fixture {}
+    "#);
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let it_works = id_of(&db, &def_map, "test_it_works", ItemKind::Function).unwrap();
+    assert_eq!(def_map.test_functions(), vec![it_works]);
+}
+
+#[test]
+fn test_builtin_functions() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", "fun main() {}");
+
+    let mut resolver = Resolver::new(&db)
+        .with_builtin("print", BuiltinSignature::new(vec![Type::String], None));
+    resolver.collect_crate(main_fid).unwrap();
+    let def_map = resolver.into_def_map();
+
+    // 不需要 `use` 就能解析到
+    let print = def_map.builtin(db.intern_string("print")).unwrap();
+    assert_eq!(def_map.resolve_name(main_fid, db.intern_string("print")), Some(Binding::Def(print)));
+    // 内建函数没有文件，也不出现在 `items` 里；新收集的定义不会和它共用 DefId
+    assert_eq!(def_map.file_of(print), None);
+    let main = id_of(&db, &def_map, "main", ItemKind::Function).unwrap();
+    assert_ne!(main, print);
+    assert!(def_map.validate().is_ok());
+    assert_eq!(def_map.to_golden_string(&db), "fn main @ main.ny\n");
+
+    // 按签名检查调用
+    let signature = &def_map.builtins[&print].signature;
+    assert_eq!(signature.check_call(&[Type::String]), Ok(None));
+    let error = signature.check_call(&[Type::Int]).unwrap_err();
+    assert_eq!(error, CallError::ArgType { index: 0, expected: Type::String, found: Type::Int });
+    // 调用方决定类型的拼写
+    let type_name = |ty: &Type| String::from(if *ty == Type::String { "String" } else { "Int" });
+    assert_eq!(error.message(type_name), "argument 1 has type `Int`, expected `String`");
+
+    let error = signature.check_call(&[]).unwrap_err();
+    assert_eq!(error.message(type_name), "this function takes 1 argument but 0 were supplied");
+}
+
+#[test]
+fn test_qualified_cache_hits_on_repeated_paths() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+
+    let path = [db.intern_string("utils"), db.intern_string("helper")];
+    let helper = id_of(&db, &def_map, "helper", ItemKind::Function);
+
+    let mut cache = QualifiedCache::new();
+    assert_eq!(cache.resolve(&def_map, main_fid, &path), helper);
+    assert_eq!(cache.resolve(&def_map, main_fid, &path), helper);
+    assert_eq!(cache.misses(), 1);
+
+    // 不存在的路径同样被缓存
+    let missing = [db.intern_string("utils"), db.intern_string("nope")];
+    assert_eq!(cache.resolve(&def_map, main_fid, &missing), None);
+    assert_eq!(cache.resolve(&def_map, main_fid, &missing), None);
+    assert_eq!(cache.misses(), 2);
+
+    // `utils` 没有导入它自己，所以在 utils.ny 中无法解析
+    assert_eq!(cache.resolve(&def_map, utils_fid, &path), None);
+
+    // 文件重新分析后失效
+    cache.invalidate_file(main_fid);
+    assert_eq!(cache.resolve(&def_map, main_fid, &path), helper);
+    assert_eq!(cache.misses(), 4);
+}
+
+#[test]
+fn test_update_file_skips_identical_content() {
+    let mut db = MockDb::default();
+    let source = r#"
+        use utils
+        fun main() {}
+    "#;
+    let main_fid = db.add_file("main.ny", source);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let main = id_of(&db, &def_map, "main", ItemKind::Function);
+
+    // 保存了一次，但内容没有任何变化
+    db.set_source(main_fid, source);
+    let mut resolver = Resolver::with_def_map(&db, def_map);
+    db.intern_calls.set(0);
+    resolver.update_file(main_fid).unwrap();
+    assert_eq!(db.intern_calls.get(), 0);
+    assert!(db.ast_cache.borrow().get(&main_fid).is_none());
+    let def_map = resolver.into_def_map();
+    assert_eq!(def_map.lookup(db.intern_string("main"), ItemKind::Function), main);
+
+    // 真正的修改仍然会重新收集
+    db.set_source(main_fid, "fun renamed() {}");
+    let mut resolver = Resolver::with_def_map(&db, def_map);
+    resolver.update_file(main_fid).unwrap();
+    assert!(db.intern_calls.get() > 0);
+    assert!(id_of(&db, &resolver.into_def_map(), "renamed", ItemKind::Function).is_some());
+}
+
+#[test]
+fn test_cancelled_collection_returns_no_def_map() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = Resolver::new(&db).with_cancellation(&token).collect_defs_crate(main_fid);
+    assert_eq!(result.unwrap_err(), Cancelled);
+}
+
+#[test]
+fn test_cancelled_update_keeps_old_fragment() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        fun helper() {}
+    "#);
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let helper = id_of(&db, &def_map, "helper", ItemKind::Function);
+
+    db.set_source(main_fid, "fun main() {}");
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut resolver = Resolver::with_def_map(&db, def_map).with_cancellation(&token);
+    assert_eq!(resolver.update_file(main_fid), Err(Cancelled));
+
+    // 旧的定义仍然在
+    let def_map = resolver.into_def_map();
+    assert_eq!(id_of(&db, &def_map, "helper", ItemKind::Function), helper);
+    assert_eq!(def_map.files[&main_fid].items.len(), 2);
+    assert!(def_map.validate().is_ok());
+}
+
+#[test]
+fn test_update_file_does_not_move_ids_to_new_imports() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        fun main() {}
+        fun helper() {}
+    "#);
+    let other_fid = db.add_file("other.ny", "fun helper() {}");
+    let (def_map, _) = Resolver::new(&db).collect_defs_crate(main_fid).unwrap();
+    let old_helper = id_of(&db, &def_map, "helper", ItemKind::Function).unwrap();
+
+    // 删除本地的 helper，改为导入定义了同名函数的 other.ny
+    db.set_source(main_fid, r#"
+        use other
+        fun main() {}
+    "#);
+    let mut resolver = Resolver::with_def_map(&db, def_map);
+    resolver.update_file(main_fid).unwrap();
+    let def_map = resolver.into_def_map();
+
+    // other.ny 的 helper 拿到的是新 id，而不是 main.ny 中被删除的那个
+    let new_helper = def_map.files[&other_fid].items[0];
+    assert_ne!(new_helper, old_helper);
+    assert!(!def_map.items.contains_key(&old_helper));
+    assert!(def_map.validate().is_ok());
+}
+
+#[test]
+fn test_import_closure_handles_long_chains() {
+    // 一条很长的导入链：f0 -> f1 -> ... -> f4999
+    const LEN: usize = 5_000;
+    let mut db = MockDb::default();
+    let ids: Vec<FileId> = (0..LEN)
+        .map(|i| {
+            let source = if i + 1 < LEN { format!("use f{}", i + 1) } else { String::new() };
+            db.add_file(&format!("f{}.ny", i), &source)
+        })
+        .collect();
+
+    let closure = import_closure(&db, ids[0]);
+    assert_eq!(closure.order.len(), LEN);
+    assert_eq!(closure.order.first(), ids.last());
+    assert_eq!(closure.order.last(), ids.first());
+    assert!(closure.cycles.is_empty());
+}
+
+#[test]
+fn test_collect_defs_crate_returns_load_failures() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use broken
+        use utils
+        fun main() {}
+    "#);
+    let prelude_fid = db.add_file("prelude.ny", "use broken");
+    db.add_file("utils.ny", "fun helper() {}");
+    let broken_fid = db.add_file("broken.ny", "fun broken( {");
+    db.parse_failures.insert(broken_fid);
+
+    // prelude 和入口文件都导入了 `broken`，加载失败只报告一次，其余文件照常收集
+    let (def_map, diagnostics) = Resolver::new(&db)
+        .with_prelude(prelude_fid)
+        .collect_defs_crate(main_fid)
+        .unwrap();
+    let messages: Vec<_> = diagnostics.iter().map(|diag| diag.message.as_str()).collect();
+    assert_eq!(messages, vec!["failed to load `broken.ny`"]);
+    assert_eq!(diagnostics[0].file_id, Some(broken_fid));
+    assert!(id_of(&db, &def_map, "main", ItemKind::Function).is_some());
+    assert!(id_of(&db, &def_map, "helper", ItemKind::Function).is_some());
+}
+
+#[test]
+fn test_update_file_does_not_reload_failed_imports() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use broken
+        fun main() {}
+    "#);
+    let broken_fid = db.add_file("broken.ny", "fun broken( {");
+    db.parse_failures.insert(broken_fid);
+
+    let mut resolver = Resolver::new(&db);
+    resolver.collect_crate(main_fid).unwrap();
+    let snapshot = resolver.snapshot();
+    drop(resolver);
+
+    // 快照带着加载失败的记录：更新导入它的文件时不会再次加载、再次报告
+    db.set_source(main_fid, "use broken\nfun main() {}\nfun helper() {}\n");
+    let mut resolver = Resolver::new(&db);
+    resolver.restore(snapshot);
+    resolver.update_file(main_fid).unwrap();
+    let (_, diagnostics) = resolver.finish();
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_repeated_resolution_does_not_allocate() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        use crate::geo::shapes
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+    let shapes_fid = db.add_file("geo/shapes.ny", "struct Point {}");
+
+    // 收集时每条路径已经解析过一次
+    let mut resolver = Resolver::new(&db);
+    resolver.collect_crate(main_fid).unwrap();
+
+    let ast = db.ast(main_fid).unwrap();
+    let paths: Vec<&ast::Path> = ast.items.iter()
+        .filter_map(|item| match item {
+            ast::Item::Use(use_stmt) => match &use_stmt.tree {
+                ast::UseTree::Simple { path, .. } => Some(path),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    let (resolved, allocations) = count_allocations(|| {
+        [resolver.resolve_use_path(main_fid, paths[0]), resolver.resolve_use_path(main_fid, paths[1])]
+    });
+    assert_eq!(resolved, [ModuleResolution::Found(utils_fid), ModuleResolution::Found(shapes_fid)]);
+    assert_eq!(allocations, 0);
+}
@@ -0,0 +1,59 @@
+use super::mock_db::MockDb;
+use crate::check::check_crate;
+use crate::diagnostics::Severity;
+use crate::suggest::{closest_match, edit_distance};
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("helper", "helper"), 0);
+    assert_eq!(edit_distance("helpr", "helper"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn test_closest_match_requires_a_near_miss() {
+    let candidates = ["helper", "main", "Point"];
+    assert_eq!(closest_match("helpr", candidates), Some("helper"));
+    assert_eq!(closest_match("completely_different", candidates), None);
+}
+
+#[test]
+fn test_did_you_mean_for_unresolved_import() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        use utlis
+        fun main() {}
+    "#);
+    db.add_file("utils.ny", "fun helper() {}");
+
+    let result = check_crate(&db, main_fid);
+    let hints: Vec<_> = result.all()
+        .filter(|diag| diag.severity == Severity::Hint)
+        .map(|diag| diag.message.as_str())
+        .collect();
+    assert_eq!(hints, vec!["did you mean `utils`?"]);
+}
+
+#[test]
+fn test_import_suggestions_only_name_reachable_modules() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use net::client
+        use helpr
+        use clinet
+        use net::clinet
+        fun helper() {}
+        fun main() {}
+    "#);
+    db.add_file("net/client.ny", "fun connect() {}");
+
+    let result = check_crate(&db, main_fid);
+    let hints: Vec<_> = result.all()
+        .filter(|diag| diag.severity == Severity::Hint)
+        .map(|diag| diag.message.as_str())
+        .collect();
+    // `helper` 是函数，`client` 不在 crate 根下，只有 `net::clinet` 能得到提示
+    assert_eq!(hints, vec!["did you mean `client`?"]);
+}
@@ -0,0 +1,84 @@
+use super::mock_db::MockDb;
+use crate::resolver::Resolver;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// 一个被记录下来的 span：名字，以及创建时和之后 `record` 的字段
+#[derive(Debug, Clone)]
+struct RecordedSpan {
+    id: u64,
+    name: &'static str,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl RecordedSpan {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(field, _)| *field == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<(&'static str, String)>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push((field.name(), format!("{:?}", value)));
+    }
+}
+
+/// 记录所有新建 span 及其字段的测试 Layer
+#[derive(Clone, Default)]
+struct SpanRecorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+impl SpanRecorder {
+    fn named(&self, name: &str) -> Vec<RecordedSpan> {
+        self.0.lock().unwrap().iter().filter(|span| span.name == name).cloned().collect()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SpanRecorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let mut fields = Vec::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        self.0.lock().unwrap().push(RecordedSpan { id: id.into_u64(), name: attrs.metadata().name(), fields });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut spans = self.0.lock().unwrap();
+        if let Some(span) = spans.iter_mut().rev().find(|span| span.id == id.into_u64()) {
+            values.record(&mut FieldVisitor(&mut span.fields));
+        }
+    }
+}
+
+#[test]
+fn test_span_per_visited_file() {
+    let mut db = MockDb::default();
+    let main_fid = db.add_file("main.ny", r#"
+        use utils
+        fun main() {}
+    "#);
+    let utils_fid = db.add_file("utils.ny", "fun helper() {}");
+    let prelude_fid = db.add_file("prelude.ny", "fun print() {}");
+
+    let recorder = SpanRecorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        Resolver::new(&db).with_prelude(prelude_fid).collect_defs_crate(main_fid).unwrap();
+    });
+
+    // 整次收集只有一个 span，prelude 不会多出一个
+    let crate_spans = recorder.named("collect_crate");
+    assert_eq!(crate_spans.len(), 1);
+    assert_eq!(crate_spans[0].field("entry_file"), Some(main_fid.to_string().as_str()));
+
+    assert_eq!(recorder.named("collect_file").len(), 3);
+
+    let resolve_spans = recorder.named("resolve_module");
+    assert_eq!(resolve_spans.len(), 1);
+    assert_eq!(resolve_spans[0].field("anchor_file"), Some(main_fid.to_string().as_str()));
+    assert_eq!(resolve_spans[0].field("resolved"), Some(utils_fid.to_string().as_str()));
+}
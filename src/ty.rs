@@ -3,6 +3,61 @@
 use hir::{HirId, Type};
 use std::collections::HashMap;
 
+/// 内建函数的签名。内建函数没有源码，签名由注册它的一方给出。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltinSignature {
+    pub params: Vec<Type>,
+    /// 没有返回值时为 `None`
+    pub ret: Option<Type>,
+}
+
+/// 调用与签名不符时产生的错误。
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallError {
+    ArgCount { expected: usize, found: usize },
+    ArgType { index: usize, expected: Type, found: Type },
+}
+
+impl CallError {
+    /// 面向用户的错误信息。类型怎样写出来由调用方决定（通常是源码中的拼写）
+    pub fn message(&self, type_name: impl Fn(&Type) -> String) -> String {
+        match self {
+            CallError::ArgCount { expected, found } => format!(
+                "this function takes {} argument{} but {} {} supplied",
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found,
+                if *found == 1 { "was" } else { "were" },
+            ),
+            CallError::ArgType { index, expected, found } => format!(
+                "argument {} has type `{}`, expected `{}`",
+                index + 1,
+                type_name(found),
+                type_name(expected),
+            ),
+        }
+    }
+}
+
+impl BuiltinSignature {
+    pub fn new(params: Vec<Type>, ret: Option<Type>) -> Self {
+        Self { params, ret }
+    }
+
+    /// 检查一次调用的实参类型，成功时返回调用结果的类型
+    pub fn check_call(&self, args: &[Type]) -> Result<Option<Type>, CallError> {
+        if args.len() != self.params.len() {
+            return Err(CallError::ArgCount { expected: self.params.len(), found: args.len() });
+        }
+        for (index, (param, arg)) in self.params.iter().zip(args).enumerate() {
+            if param != arg {
+                return Err(CallError::ArgType { index, expected: param.clone(), found: arg.clone() });
+            }
+        }
+        Ok(self.ret.clone())
+    }
+}
+
 /// TypeMap 是类型检查阶段的核心产物。
 /// 它将每一个表达式（甚至未来每一个变量声明）的 HirId 映射到其推断出的具体类型。
 #[derive(Debug, Default)]
@@ -0,0 +1,23 @@
+// analyzer/src/visit.rs
+
+use crate::resolver::{DefMap, ItemDef, ItemKind};
+use hir::DefId;
+
+/// 遍历 DefMap 中收集到的定义。每种定义都有一个默认什么都不做的方法，
+/// lint 和统计工具只需要覆盖它关心的那几种。
+pub trait DefVisitor {
+    fn visit_function(&mut self, _def_id: DefId, _item_def: &ItemDef) {}
+    fn visit_struct(&mut self, _def_id: DefId, _item_def: &ItemDef) {}
+}
+
+impl DefMap {
+    /// 按 DefId 升序，把每个定义分派给 `visitor` 中对应种类的方法
+    pub fn walk<V: DefVisitor + ?Sized>(&self, visitor: &mut V) {
+        for (def_id, item_def) in self.iter() {
+            match item_def.kind {
+                ItemKind::Function => visitor.visit_function(def_id, item_def),
+                ItemKind::Struct => visitor.visit_struct(def_id, item_def),
+            }
+        }
+    }
+}